@@ -0,0 +1,117 @@
+use crate::{host::Host, stable_hash};
+use std::fs;
+use zed_extension_api::{self as zed, serde_json, Result};
+
+/// Starts the error for a GitHub API response that isn't the JSON asked for, so it can be told
+/// apart from failing to fetch it at all.
+pub const MALFORMED_RESPONSE: &str = "malformed response from";
+
+/// Fetches a GitHub API endpoint. The extension API has no HTTP client, so the response is
+/// downloaded to a temporary file and read back.
+pub fn fetch_json(host: &impl Host, url: &str) -> Result<serde_json::Value> {
+    let path = format!("github-api-{:016x}.json", stable_hash(url));
+    host.download_file(url, &path, zed::DownloadedFileType::Uncompressed)?;
+    let contents = fs::read_to_string(&path);
+    fs::remove_file(&path).ok();
+    serde_json::from_str(&contents.map_err(|err| format!("failed to read {path}: {err}"))?)
+        .map_err(|err| format!("{MALFORMED_RESPONSE} {url}: {err}"))
+}
+
+/// Fetches the release tagged `tag`, which `latest_github_release` can't return unless it
+/// happens to be the latest.
+pub fn release_by_tag(host: &impl Host, repository: &str, tag: &str) -> Result<zed::GithubRelease> {
+    let url = format!("https://api.github.com/repos/{repository}/releases/tags/{tag}");
+    let release = fetch_json(host, &url)?;
+    parse_release(&release).ok_or_else(|| format!("{repository} has no release tagged {tag}"))
+}
+
+/// A release from the releases list, which unlike Zed's release lookups says whether it is a
+/// pre-release.
+pub struct ListedRelease {
+    pub release: zed::GithubRelease,
+    pub pre_release: bool,
+}
+
+/// Fetches up to `count` of the most recent releases, newest first, a page of at most 100 at a
+/// time. Drafts are only listed for users with push access, so they don't show up here.
+pub fn list_releases(
+    host: &impl Host,
+    repository: &str,
+    count: usize,
+) -> Result<Vec<ListedRelease>> {
+    const MAX_PER_PAGE: usize = 100;
+    let mut releases = Vec::new();
+    for page in 1.. {
+        let per_page = count.saturating_sub(releases.len()).min(MAX_PER_PAGE);
+        if per_page == 0 {
+            break;
+        }
+        let url = format!(
+            "https://api.github.com/repos/{repository}/releases?per_page={per_page}&page={page}"
+        );
+        let response = fetch_json(host, &url)?;
+        let page = response
+            .as_array()
+            .ok_or_else(|| format!("{MALFORMED_RESPONSE} {url}: not a list of releases"))?;
+        // A proxy or mirror may not honor per_page, so never keep more than was asked for
+        releases.extend(page.iter().take(per_page).filter_map(|release| {
+            Some(ListedRelease {
+                release: parse_release(release)?,
+                pre_release: release.get("prerelease")?.as_bool()?,
+            })
+        }));
+        if page.len() < per_page {
+            break;
+        }
+    }
+    Ok(releases)
+}
+
+/// The release the GitHub API describes in `release`, in the shape Zed's own release lookups
+/// return.
+fn parse_release(release: &serde_json::Value) -> Option<zed::GithubRelease> {
+    let version = release.get("tag_name")?.as_str()?.to_string();
+    let assets = release
+        .get("assets")?
+        .as_array()?
+        .iter()
+        .filter_map(|asset| {
+            Some(zed::GithubReleaseAsset {
+                name: asset.get("name")?.as_str()?.to_string(),
+                download_url: asset.get("browser_download_url")?.as_str()?.to_string(),
+            })
+        })
+        .collect();
+    Some(zed::GithubRelease { version, assets })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::FakeHost;
+    use std::collections::HashMap;
+
+    #[test]
+    fn list_releases_keeps_at_most_count() {
+        let repository = "example/oversized-page";
+        let page = serde_json::json!([
+            {"tag_name": "c", "prerelease": false, "assets": []},
+            {"tag_name": "b", "prerelease": true, "assets": []},
+            {"tag_name": "a", "prerelease": false, "assets": []},
+        ]);
+        let host = FakeHost {
+            // A mirror that ignores per_page
+            downloads: HashMap::from([(
+                format!("https://api.github.com/repos/{repository}/releases?per_page=2&page=1"),
+                page.to_string().into_bytes(),
+            )]),
+            ..FakeHost::default()
+        };
+        let releases = list_releases(&host, repository, 2).unwrap();
+        let tags: Vec<_> = releases
+            .iter()
+            .map(|listed| (listed.release.version.as_str(), listed.pre_release))
+            .collect();
+        assert_eq!(tags, [("c", false), ("b", true)]);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{
+    detect_odin_version, find_asset, github,
+    host::Host,
+    init_options::{self, OLS_CONFIG_FILE},
+    server::LanguageServer,
+    OlsBinary, RECENT_RELEASE_COUNT,
+};
+use std::fmt::Write;
+use zed_extension_api::serde_json;
+
+/// Builds the report written when `health_check` is enabled: everything a bug report about ols
+/// not starting or misbehaving usually needs, one `key: value` line each. Releases are listed
+/// from `repository`.
+pub fn report(
+    host: &impl Host,
+    server: &dyn LanguageServer,
+    repository: &str,
+    binary: &OlsBinary,
+) -> String {
+    let environment = binary.environment.as_deref();
+    let (os, arch) = host.current_platform();
+    let odin_root = environment
+        .unwrap_or_default()
+        .iter()
+        .find(|(key, _)| key == "ODIN_ROOT")
+        .map(|(_, value)| value.as_str());
+    let ols_config = match host.read_text_file(OLS_CONFIG_FILE) {
+        Ok(contents) => match serde_json::from_str(&contents) {
+            Ok(config) => match init_options::problems(&config).as_slice() {
+                [] => "valid".to_string(),
+                problems => problems.join("; "),
+            },
+            Err(err) => format!("invalid JSON ({err})"),
+        },
+        Err(_) => "not present".to_string(),
+    };
+    // Recent releases, as candidates for `version` and `channel`; a failed lookup doubles as the
+    // GitHub reachability check
+    let stems = server.asset_stems(os, arch);
+    let releases = match github::list_releases(host, repository, RECENT_RELEASE_COUNT) {
+        Ok(releases) => releases
+            .into_iter()
+            .map(|listed| {
+                let channel = if listed.pre_release {
+                    "pre-release"
+                } else {
+                    "stable"
+                };
+                let asset =
+                    match find_asset(&listed.release, &stems, None, server.archive_format(os)) {
+                        Some((asset, _)) => asset.name.clone(),
+                        None => "no asset for this platform".to_string(),
+                    };
+                (
+                    format!("release {}", listed.release.version),
+                    format!("{channel}, {asset}"),
+                )
+            })
+            .collect(),
+        Err(err) => vec![(
+            "releases".to_string(),
+            format!("GitHub unreachable ({err})"),
+        )],
+    };
+
+    let mut report = String::new();
+    let lines = [
+        ("platform", format!("{os:?} {arch:?}")),
+        ("binary", binary.path.clone()),
+        ("source", binary.source.describe().to_string()),
+        (
+            "version",
+            binary.version.clone().unwrap_or("unknown".to_string()),
+        ),
+        (
+            "odin",
+            host.which("odin").unwrap_or("not on PATH".to_string()),
+        ),
+        (
+            "odin release",
+            detect_odin_version(host, environment).unwrap_or("unknown".to_string()),
+        ),
+        ("ODIN_ROOT", odin_root.unwrap_or("not set").to_string()),
+        (OLS_CONFIG_FILE, ols_config),
+    ];
+    let lines = lines
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .chain(releases);
+    for (key, value) in lines {
+        writeln!(report, "{key}: {value}").ok();
+    }
+    report
+}
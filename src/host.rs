@@ -0,0 +1,221 @@
+use zed::LanguageServerId;
+use zed_extension_api::{self as zed, Result};
+#[cfg(test)]
+use {
+    std::{cell::RefCell, collections::HashMap, fs},
+    zed_extension_api::serde_json,
+};
+
+/// The side effects binary resolution and initialization options depend on, so they can run
+/// against something other than a live Zed host.
+pub trait Host {
+    /// Returns the current operating system and architecture.
+    fn current_platform(&self) -> (zed::Os, zed::Architecture);
+
+    /// Returns the path to the given binary name, if one is present on the worktree's `$PATH`.
+    fn which(&self, binary_name: &str) -> Option<String>;
+
+    /// Returns the worktree's shell environment.
+    fn shell_env(&self) -> Vec<(String, String)>;
+
+    /// Returns the contents of a file, relative to the worktree root.
+    fn read_text_file(&self, path: &str) -> Result<String>;
+
+    /// Returns the latest release for the given GitHub repository.
+    fn latest_github_release(
+        &self,
+        repo: &str,
+        options: zed::GithubReleaseOptions,
+    ) -> Result<zed::GithubRelease>;
+
+    /// Downloads and extracts `url` to `path`.
+    fn download_file(
+        &self,
+        url: &str,
+        path: &str,
+        file_type: zed::DownloadedFileType,
+    ) -> Result<()>;
+
+    /// Makes the file at `path` executable.
+    fn make_file_executable(&self, path: &str) -> Result<()>;
+
+    /// Updates the installation status shown for the language server.
+    fn set_installation_status(&self, status: &zed::LanguageServerInstallationStatus);
+}
+
+/// The [`Host`] backed by the Zed extension API, for one language server and worktree.
+pub struct ZedHost<'a> {
+    pub language_server_id: &'a LanguageServerId,
+    pub worktree: &'a zed::Worktree,
+}
+
+impl Host for ZedHost<'_> {
+    fn current_platform(&self) -> (zed::Os, zed::Architecture) {
+        zed::current_platform()
+    }
+
+    fn which(&self, binary_name: &str) -> Option<String> {
+        self.worktree.which(binary_name)
+    }
+
+    fn shell_env(&self) -> Vec<(String, String)> {
+        self.worktree.shell_env()
+    }
+
+    fn read_text_file(&self, path: &str) -> Result<String> {
+        self.worktree.read_text_file(path)
+    }
+
+    fn latest_github_release(
+        &self,
+        repo: &str,
+        options: zed::GithubReleaseOptions,
+    ) -> Result<zed::GithubRelease> {
+        zed::latest_github_release(repo, options)
+    }
+
+    fn download_file(
+        &self,
+        url: &str,
+        path: &str,
+        file_type: zed::DownloadedFileType,
+    ) -> Result<()> {
+        zed::download_file(url, path, file_type)
+    }
+
+    fn make_file_executable(&self, path: &str) -> Result<()> {
+        zed::make_file_executable(path)
+    }
+
+    fn set_installation_status(&self, status: &zed::LanguageServerInstallationStatus) {
+        zed::set_language_server_installation_status(self.language_server_id, status)
+    }
+}
+
+/// A [`Host`] for tests: a Linux x86_64 machine with the given PATH, environment and worktree
+/// files, and GitHub repositories with the given releases. Downloads write real files, so tests
+/// point `cache_dir` at a temporary directory.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeHost {
+    /// Binaries on the worktree's PATH, by name.
+    pub path: HashMap<String, String>,
+    pub shell_env: Vec<(String, String)>,
+    /// Worktree files, by path relative to the root.
+    pub files: HashMap<String, String>,
+    /// Releases of each repository, newest first, with whether each is a pre-release. The GitHub
+    /// API endpoints for them are served as `download_file` downloads.
+    pub releases: HashMap<String, Vec<(zed::GithubRelease, bool)>>,
+    /// Contents of each download URL, overriding any GitHub API response. An archive is extracted
+    /// to a single file named after the asset without its extension.
+    pub downloads: HashMap<String, Vec<u8>>,
+    /// URLs downloaded so far, including failed attempts.
+    pub downloaded: RefCell<Vec<String>>,
+    /// Installation statuses set so far.
+    pub statuses: RefCell<Vec<String>>,
+}
+
+#[cfg(test)]
+impl FakeHost {
+    fn api_response(&self, url: &str) -> Option<serde_json::Value> {
+        let to_json = |(release, pre_release): &(zed::GithubRelease, bool)| {
+            serde_json::json!({
+                "tag_name": release.version,
+                "prerelease": pre_release,
+                "assets": release.assets.iter().map(|asset| serde_json::json!({
+                    "name": asset.name,
+                    "browser_download_url": asset.download_url,
+                })).collect::<Vec<_>>(),
+            })
+        };
+        let rest = url.strip_prefix("https://api.github.com/repos/")?;
+        let (repo, endpoint) = rest.split_once("/releases")?;
+        let releases = self.releases.get(repo)?;
+        if let Some(tag) = endpoint.strip_prefix("/tags/") {
+            return releases.iter().find(|(r, _)| r.version == tag).map(to_json);
+        }
+        let query = endpoint.strip_prefix('?')?;
+        let param = |name: &str| {
+            query.split('&').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                (key == name).then(|| value.parse::<usize>().ok())?
+            })
+        };
+        let (per_page, page) = (param("per_page")?, param("page").unwrap_or(1));
+        let page = releases.iter().skip(per_page * (page - 1)).take(per_page);
+        Some(page.map(to_json).collect())
+    }
+}
+
+#[cfg(test)]
+impl Host for FakeHost {
+    fn current_platform(&self) -> (zed::Os, zed::Architecture) {
+        (zed::Os::Linux, zed::Architecture::X8664)
+    }
+
+    fn which(&self, binary_name: &str) -> Option<String> {
+        self.path.get(binary_name).cloned()
+    }
+
+    fn shell_env(&self) -> Vec<(String, String)> {
+        self.shell_env.clone()
+    }
+
+    fn read_text_file(&self, path: &str) -> Result<String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| format!("no such file: {path}"))
+    }
+
+    fn latest_github_release(
+        &self,
+        repo: &str,
+        options: zed::GithubReleaseOptions,
+    ) -> Result<zed::GithubRelease> {
+        self.releases
+            .get(repo)
+            .into_iter()
+            .flatten()
+            .filter(|(_, pre_release)| options.pre_release || !pre_release)
+            .find(|(release, _)| !options.require_assets || !release.assets.is_empty())
+            .map(|(release, _)| release.clone())
+            .ok_or_else(|| format!("no release found for {repo}"))
+    }
+
+    fn download_file(
+        &self,
+        url: &str,
+        path: &str,
+        file_type: zed::DownloadedFileType,
+    ) -> Result<()> {
+        self.downloaded.borrow_mut().push(url.to_string());
+        let contents = match self.downloads.get(url) {
+            Some(contents) => contents.clone(),
+            None => self
+                .api_response(url)
+                .map(|response| response.to_string().into_bytes())
+                .ok_or_else(|| format!("status error 404 downloading {url}"))?,
+        };
+        let path = match file_type {
+            zed::DownloadedFileType::Zip | zed::DownloadedFileType::GzipTar => {
+                let file_name = url.rsplit('/').next().unwrap_or(url);
+                let stem = file_name.split('.').next().unwrap_or(file_name);
+                fs::create_dir_all(path).map_err(|err| err.to_string())?;
+                format!("{path}/{stem}")
+            }
+            zed::DownloadedFileType::Gzip | zed::DownloadedFileType::Uncompressed => {
+                path.to_string()
+            }
+        };
+        fs::write(path, contents).map_err(|err| err.to_string())
+    }
+
+    fn make_file_executable(&self, _path: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_installation_status(&self, status: &zed::LanguageServerInstallationStatus) {
+        self.statuses.borrow_mut().push(format!("{status:?}"));
+    }
+}
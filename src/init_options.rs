@@ -0,0 +1,465 @@
+use crate::host::Host;
+use crate::settings::{CheckOn, OdinSettings};
+use crate::{log, stable_hash};
+use std::fs;
+use zed_extension_api::{self as zed, serde_json, settings::LspSettings};
+
+/// The config file ols reads from the workspace root.
+pub const OLS_CONFIG_FILE: &str = "ols.json";
+
+/// ols options a team commits to the repository for the extension to send, in the worktree.
+const PROJECT_CONFIG_FILE: &str = ".zed/ols.json";
+
+/// The JSON type expected for an ols option.
+#[derive(Clone, Copy)]
+enum OptionType {
+    Bool,
+    Integer,
+    String,
+    Array,
+}
+
+impl OptionType {
+    fn matches(self, value: &serde_json::Value) -> bool {
+        match self {
+            OptionType::Bool => value.is_boolean(),
+            OptionType::Integer => value.is_i64() || value.is_u64(),
+            OptionType::String => value.is_string(),
+            OptionType::Array => value.is_array(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OptionType::Bool => "a boolean",
+            OptionType::Integer => "an integer",
+            OptionType::String => "a string",
+            OptionType::Array => "an array",
+        }
+    }
+}
+
+/// The top-level keys ols accepts in `ols.json`, with their types.
+const OLS_OPTIONS: &[(&str, OptionType)] = &[
+    ("$schema", OptionType::String),
+    ("collections", OptionType::Array),
+    ("thread_pool_count", OptionType::Integer),
+    ("enable_semantic_tokens", OptionType::Bool),
+    ("enable_document_symbols", OptionType::Bool),
+    ("enable_format", OptionType::Bool),
+    ("enable_hover", OptionType::Bool),
+    ("enable_procedure_context", OptionType::Bool),
+    ("enable_snippets", OptionType::Bool),
+    ("enable_inlay_hints", OptionType::Bool),
+    ("enable_inlay_hints_params", OptionType::Bool),
+    ("enable_inlay_hints_default_params", OptionType::Bool),
+    ("enable_references", OptionType::Bool),
+    ("enable_fake_methods", OptionType::Bool),
+    ("enable_procedure_snippet", OptionType::Bool),
+    ("enable_checker_only_saved", OptionType::Bool),
+    ("disable_parser_errors", OptionType::Bool),
+    ("verbose", OptionType::Bool),
+    ("file_log", OptionType::Bool),
+    ("odin_command", OptionType::String),
+    ("checker_args", OptionType::String),
+    ("profile", OptionType::String),
+    ("profiles", OptionType::Array),
+];
+
+/// The first ols monthly release each option the extension's own settings can send is known to
+/// work in, for warning when an older ols would ignore it. Options that predate the tested range
+/// are listed at its start; add new ones with the release that introduced them.
+const OPTION_RELEASES: &[(&str, &str)] = &[
+    ("enable_checker_only_saved", "dev-2024-04"),
+    ("enable_references", "dev-2024-04"),
+    ("enable_fake_methods", "dev-2024-04"),
+    ("enable_inlay_hints_params", "dev-2024-04"),
+    ("enable_inlay_hints_default_params", "dev-2024-04"),
+    ("enable_procedure_snippet", "dev-2024-04"),
+    ("checker_args", "dev-2024-04"),
+];
+
+/// Lists the options in `options` that ols `release` (a `dev-YYYY-MM` name) predates, with the
+/// release each needs. `dev-YYYY-MM` names sort chronologically, so they're compared as strings.
+pub fn unsupported_options(
+    options: &serde_json::Value,
+    release: &str,
+) -> Vec<(&'static str, &'static str)> {
+    OPTION_RELEASES
+        .iter()
+        .copied()
+        .filter(|(option, since)| options.get(option).is_some() && release < *since)
+        .collect()
+}
+
+/// Builds the initialization options sent to ols, which accepts the same keys as `ols.json`.
+///
+/// Options are layered, later layers winning: the `ols_json_template_url` template (only without
+/// a workspace `ols.json`), the team's committed `.zed/ols.json`, the file at `config_path`, the
+/// safe mode and check-only profiles, `check_on`, `references`, the `experimental` flags, the
+/// user's `initialization_options`, then `raw_initialization_options`. `target` and `defines` are
+/// appended to whichever `checker_args` wins.
+pub fn initialization_options(
+    host: &impl Host,
+    lsp_settings: &LspSettings,
+    settings: &OdinSettings,
+) -> Option<serde_json::Value> {
+    let mut options = None;
+
+    // ols loads `ols.json` from the workspace itself; check it so mistakes show up in the log.
+    let ols_config = host.read_text_file(OLS_CONFIG_FILE);
+    let mut workspace_config = None;
+    if let Ok(contents) = &ols_config {
+        match serde_json::from_str(contents) {
+            Ok(config) => {
+                validate(OLS_CONFIG_FILE, &config);
+                workspace_config = Some(config);
+            }
+            Err(err) => log(&format!(
+                "warning: {OLS_CONFIG_FILE} is not valid JSON: {err}"
+            )),
+        }
+    }
+
+    // Teams can share a standard config with worktrees that don't have one of their own
+    if let (Err(_), Some(url)) = (&ols_config, &settings.ols_json_template_url) {
+        match template(host, url) {
+            Ok(template) => options = Some(template),
+            Err(err) => log(&format!("warning: ignoring ols_json_template_url: {err}")),
+        }
+    }
+
+    // A baseline checked into the repository, which each contributor's own settings override
+    if let Ok(contents) = host.read_text_file(PROJECT_CONFIG_FILE) {
+        match serde_json::from_str(&contents) {
+            Ok(config) => {
+                validate(PROJECT_CONFIG_FILE, &config);
+                merge(options.get_or_insert(serde_json::json!({})), &config);
+            }
+            Err(err) => log(&format!(
+                "warning: ignoring invalid {PROJECT_CONFIG_FILE}: {err}"
+            )),
+        }
+    }
+
+    if let Some(config_path) = &settings.config_path {
+        match host.read_text_file(config_path) {
+            Ok(contents) => match serde_json::from_str(&contents) {
+                Ok(config) => {
+                    validate(config_path, &config);
+                    merge(options.get_or_insert(serde_json::json!({})), &config);
+                }
+                Err(err) => log(&format!("warning: ignoring invalid {config_path}: {err}")),
+            },
+            Err(err) => log(&format!(
+                "warning: config_path {config_path} could not be read from the worktree: {err}"
+            )),
+        }
+    }
+
+    if settings.safe_mode {
+        merge(
+            options.get_or_insert(serde_json::json!({})),
+            &safe_mode_options(),
+        );
+    }
+
+    if settings.check_only {
+        merge(
+            options.get_or_insert(serde_json::json!({})),
+            &check_only_options(),
+        );
+    }
+
+    if let Some(check_on) = settings.check_on {
+        merge(
+            options.get_or_insert(serde_json::json!({})),
+            &serde_json::json!({ "enable_checker_only_saved": check_on == CheckOn::Save }),
+        );
+    }
+
+    if let Some(references) = settings.references {
+        merge(
+            options.get_or_insert(serde_json::json!({})),
+            &serde_json::json!({ "enable_references": references }),
+        );
+    }
+
+    if !settings.experimental.is_empty() {
+        merge(
+            options.get_or_insert(serde_json::json!({})),
+            &serde_json::Value::Object(settings.experimental.clone()),
+        );
+    }
+
+    if let Some(user_options) = &lsp_settings.initialization_options {
+        merge(options.get_or_insert(serde_json::json!({})), user_options);
+    }
+
+    if let Some(raw_options) = &settings.raw_initialization_options {
+        merge(options.get_or_insert(serde_json::json!({})), raw_options);
+    }
+
+    // ols has no options for defines or the target, but passes checker_args on to `odin check`.
+    // Sending them replaces the workspace ols.json's checker_args, so those are kept in front.
+    // They're added last so checker_args set in any of the layers above doesn't drop them.
+    let checker_flags: Vec<_> = settings
+        .target
+        .iter()
+        .map(|target| format!("-target:{target}"))
+        .chain(
+            settings
+                .defines
+                .iter()
+                .map(|(name, value)| format!("-define:{name}={value}")),
+        )
+        .collect();
+    if !checker_flags.is_empty() {
+        let options = options.get_or_insert(serde_json::json!({}));
+        let mut checker_args = options
+            .get("checker_args")
+            .or_else(|| workspace_config.as_ref()?.get("checker_args"))
+            .and_then(|args| args.as_str())
+            .unwrap_or_default()
+            .to_string();
+        for flag in checker_flags {
+            if !checker_args.is_empty() {
+                checker_args.push(' ');
+            }
+            checker_args.push_str(&flag);
+        }
+        merge(
+            options,
+            &serde_json::json!({ "checker_args": checker_args }),
+        );
+    }
+
+    options
+}
+
+/// Returns the config template at `url`, downloaded once into the extension's work directory.
+fn template(host: &impl Host, url: &str) -> Result<serde_json::Value, String> {
+    let path = format!("ols-json-template-{:016x}.json", stable_hash(url));
+    if fs::metadata(&path).is_err() {
+        host.download_file(url, &path, zed::DownloadedFileType::Uncompressed)
+            .map_err(|err| format!("failed to download {url}: {err}"))?;
+    }
+    let contents =
+        fs::read_to_string(&path).map_err(|err| format!("failed to read {path}: {err}"))?;
+    let template = serde_json::from_str(&contents).map_err(|err| {
+        // Fetch it again next time rather than keeping a broken copy around
+        fs::remove_file(&path).ok();
+        format!("{url} is not valid JSON: {err}")
+    })?;
+    validate(url, &template);
+    Ok(template)
+}
+
+/// The conservative profile sent when `safe_mode` is enabled.
+///
+/// It turns off the analyses that have to understand a whole file or package at once, which are
+/// the ones known to crash on malformed code: semantic tokens, inlay hints, fake methods and
+/// find-references. Completion, hover, go-to-definition and document symbols stay on. Any of
+/// these can be turned back on through `initialization_options`.
+fn safe_mode_options() -> serde_json::Value {
+    serde_json::json!({
+        "enable_semantic_tokens": false,
+        "enable_inlay_hints": false,
+        "enable_fake_methods": false,
+        "enable_references": false,
+    })
+}
+
+/// Logs a warning for every problem [`problems`] finds. The config itself is left untouched.
+fn validate(source: &str, config: &serde_json::Value) {
+    for problem in problems(config) {
+        log(&format!("warning: {source}: {problem}"));
+    }
+}
+
+/// Lists the keys in an ols config that ols doesn't know or that have the wrong type.
+pub fn problems(config: &serde_json::Value) -> Vec<String> {
+    let Some(config) = config.as_object() else {
+        return vec!["should contain a JSON object".to_string()];
+    };
+    let mut problems = Vec::new();
+    for (key, value) in config {
+        match OLS_OPTIONS.iter().find(|(name, _)| name == key) {
+            Some((_, ty)) if !ty.matches(value) => {
+                problems.push(format!("\"{key}\" should be {}, found {value}", ty.name()))
+            }
+            Some(_) => {}
+            None => problems.push(format!("unknown ols option \"{key}\"")),
+        }
+    }
+    for (index, collection) in config
+        .get("collections")
+        .and_then(|collections| collections.as_array())
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        for field in ["name", "path"] {
+            if !collection.get(field).is_some_and(|value| value.is_string()) {
+                problems.push(format!("collections[{index}] needs a string \"{field}\""));
+            }
+        }
+    }
+    problems
+}
+
+/// The profile sent when `check_only` is enabled.
+///
+/// ols has no dedicated checking mode, so this turns off every feature ols lets you disable
+/// except diagnostics: hover, document symbols, semantic tokens, inlay hints, references,
+/// formatting, snippets and fake methods. Completion can't be turned off in ols and stays, as
+/// does go-to-definition.
+fn check_only_options() -> serde_json::Value {
+    serde_json::json!({
+        "enable_hover": false,
+        "enable_document_symbols": false,
+        "enable_semantic_tokens": false,
+        "enable_inlay_hints": false,
+        "enable_references": false,
+        "enable_format": false,
+        "enable_snippets": false,
+        "enable_procedure_context": false,
+        "enable_fake_methods": false,
+    })
+}
+
+/// Recursively merges `overlay` into `base`, with values from `overlay` taking precedence.
+pub fn merge(base: &mut serde_json::Value, overlay: &serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::FakeHost;
+    use std::collections::{BTreeMap, HashMap};
+
+    fn checker_args(host: &FakeHost, lsp_settings: &LspSettings) -> Option<String> {
+        let settings = OdinSettings {
+            target: Some("linux_arm64".to_string()),
+            defines: BTreeMap::from([("DEBUG".to_string(), "true".to_string())]),
+            ..OdinSettings::default()
+        };
+        let options = initialization_options(host, lsp_settings, &settings)?;
+        Some(options.get("checker_args")?.as_str()?.to_string())
+    }
+
+    #[test]
+    fn problems_name_unknown_and_mistyped_options() {
+        for (config, expected) in [
+            (serde_json::json!({}), vec![]),
+            (
+                serde_json::json!({ "enable_references": true, "checker_args": "-vet" }),
+                vec![],
+            ),
+            (
+                serde_json::json!([]),
+                vec!["should contain a JSON object".to_string()],
+            ),
+            (
+                serde_json::json!({ "enable_refrences": true }),
+                vec!["unknown ols option \"enable_refrences\"".to_string()],
+            ),
+            (
+                serde_json::json!({ "checker_args": ["-vet"] }),
+                vec![format!(
+                    "\"checker_args\" should be {}, found [\"-vet\"]",
+                    OptionType::String.name()
+                )],
+            ),
+            (
+                serde_json::json!({ "collections": [{ "name": "shared" }, { "path": 1 }] }),
+                vec![
+                    "collections[0] needs a string \"path\"".to_string(),
+                    "collections[1] needs a string \"name\"".to_string(),
+                    "collections[1] needs a string \"path\"".to_string(),
+                ],
+            ),
+        ] {
+            assert_eq!(problems(&config), expected, "{config}");
+        }
+    }
+
+    #[test]
+    fn options_newer_than_the_release_are_unsupported() {
+        let options = serde_json::json!({
+            "enable_references": true,
+            "enable_hover": true,
+            "checker_args": "-vet",
+        });
+        for (release, expected) in [
+            ("dev-2024-04", vec![]),
+            ("dev-2025-01", vec![]),
+            (
+                "dev-2024-03",
+                vec![
+                    ("enable_references", "dev-2024-04"),
+                    ("checker_args", "dev-2024-04"),
+                ],
+            ),
+        ] {
+            assert_eq!(
+                unsupported_options(&options, release),
+                expected,
+                "{release}"
+            );
+        }
+    }
+
+    #[test]
+    fn checker_flags_are_appended_to_the_winning_checker_args() {
+        let host = FakeHost {
+            files: HashMap::from([(
+                OLS_CONFIG_FILE.to_string(),
+                r#"{"checker_args": "-vet"}"#.to_string(),
+            )]),
+            ..FakeHost::default()
+        };
+        assert_eq!(
+            checker_args(&host, &LspSettings::default()).as_deref(),
+            Some("-vet -target:linux_arm64 -define:DEBUG=true")
+        );
+
+        let lsp_settings = LspSettings {
+            initialization_options: Some(serde_json::json!({ "checker_args": "-strict-style" })),
+            ..LspSettings::default()
+        };
+        assert_eq!(
+            checker_args(&host, &lsp_settings).as_deref(),
+            Some("-strict-style -target:linux_arm64 -define:DEBUG=true")
+        );
+
+        let lsp_settings = LspSettings {
+            settings: Some(serde_json::json!({
+                "raw_initialization_options": { "checker_args": "-warnings-as-errors" },
+            })),
+            ..lsp_settings
+        };
+        let settings = OdinSettings {
+            target: Some("linux_arm64".to_string()),
+            ..OdinSettings::from_lsp_settings(&lsp_settings).unwrap()
+        };
+        let options = initialization_options(&host, &lsp_settings, &settings).unwrap();
+        assert_eq!(
+            options["checker_args"],
+            "-warnings-as-errors -target:linux_arm64"
+        );
+    }
+}
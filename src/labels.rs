@@ -0,0 +1,717 @@
+use crate::settings::CompletionDetail;
+use std::collections::HashMap;
+use std::ops::Range;
+use zed_extension_api::{
+    lsp::{Completion, CompletionKind, Symbol, SymbolKind},
+    CodeLabel, CodeLabelSpan,
+};
+
+/// What labels of one language server are built with, remembered from its settings.
+#[derive(Default)]
+pub struct LabelSettings {
+    pub detail: CompletionDetail,
+    /// Highlight names overriding the grammar's, keyed by [`Category::key`].
+    pub highlights: HashMap<String, String>,
+    /// Whether completions show the package they come from in front of their name.
+    pub package_prefix: bool,
+}
+
+/// The part a span plays in a label, which `label_highlights` can recolor.
+#[derive(Clone, Copy)]
+enum Category {
+    Function,
+    Type,
+    Field,
+    Variable,
+    Module,
+    Constant,
+    Keyword,
+    Operator,
+    Package,
+    Detail,
+}
+
+impl Category {
+    fn key(self) -> &'static str {
+        match self {
+            Category::Function => "function",
+            Category::Type => "type",
+            Category::Field => "field",
+            Category::Variable => "variable",
+            Category::Module => "module",
+            Category::Constant => "constant",
+            Category::Keyword => "keyword",
+            Category::Operator => "operator",
+            Category::Package => "package",
+            Category::Detail => "detail",
+        }
+    }
+
+    /// Highlight of the category's plain-text spans; the others are highlighted by the grammar.
+    fn default_highlight(self) -> Option<&'static str> {
+        match self {
+            Category::Keyword => Some("keyword"),
+            Category::Operator => Some("operator"),
+            Category::Package => Some("module"),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a completion label by parsing a small piece of Odin that puts the completion in the
+/// position its kind would have in real code, so the usual syntax highlighting applies. Kinds
+/// without a sensible snippet return `None` and get Zed's plain label.
+pub fn completion_label(completion: &Completion, settings: &LabelSettings) -> Option<CodeLabel> {
+    let name = &completion.label;
+    let level = settings.detail;
+    let package = completion
+        .detail
+        .as_deref()
+        .and_then(|detail| package_of(detail, name));
+    let detail = completion
+        .detail
+        .as_deref()
+        .map(|detail| match package {
+            Some(package) => &detail[package.len() + 1..],
+            None => detail,
+        })
+        .map(|detail| strip_name(detail, name))
+        .filter(|detail| !detail.is_empty() && level != CompletionDetail::Minimal);
+    // Detail the kind's snippet has no place for, shown as plain text at the full level
+    let mut unplaced_detail = None;
+    let highlights = &settings.highlights;
+
+    let label = match completion.kind? {
+        CompletionKind::Function | CompletionKind::Method | CompletionKind::Constructor => {
+            match detail.filter(|detail| detail.starts_with("proc")) {
+                Some(signature) => {
+                    let signature_start = name.len() + " :: ".len();
+                    Label::new(highlights, format!("{name} :: {signature} {{}}"))
+                        .code(0..name.len(), Category::Function)
+                        .literal(" ", Category::Detail)
+                        .code(
+                            signature_start..signature_start + signature.len(),
+                            Category::Detail,
+                        )
+                }
+                None => {
+                    unplaced_detail = detail;
+                    Label::new(highlights, format!("{name} :: proc() {{}}"))
+                        .code(0..name.len(), Category::Function)
+                }
+            }
+        }
+        CompletionKind::Struct | CompletionKind::Class | CompletionKind::Interface => {
+            unplaced_detail = detail;
+            Label::new(highlights, format!("{name} :: struct {{}}"))
+                .code(0..name.len(), Category::Type)
+        }
+        CompletionKind::Enum => {
+            unplaced_detail = detail;
+            Label::new(highlights, format!("{name} :: enum {{}}"))
+                .code(0..name.len(), Category::Type)
+        }
+        CompletionKind::Field | CompletionKind::Property => {
+            let prefix = "_ :: struct { ";
+            let field = match detail {
+                Some(ty) => format!("{name}: {ty}"),
+                None => name.clone(),
+            };
+            Label::new(highlights, format!("{prefix}{field} }}"))
+                .code(prefix.len()..prefix.len() + field.len(), Category::Field)
+        }
+        CompletionKind::Variable => match detail {
+            Some(ty) => {
+                let code = format!("{name}: {ty}");
+                let len = code.len();
+                Label::new(highlights, code).code(0..len, Category::Variable)
+            }
+            None => Label::new(highlights, name.clone()).code(0..name.len(), Category::Variable),
+        },
+        CompletionKind::Module => {
+            unplaced_detail = detail;
+            let prefix = "import ";
+            Label::new(highlights, format!("{prefix}{name} \"\""))
+                .code(prefix.len()..prefix.len() + name.len(), Category::Module)
+        }
+        CompletionKind::Constant | CompletionKind::EnumMember => {
+            let label = Label::new(highlights, format!("{name} :: 0"))
+                .code(0..name.len(), Category::Constant);
+            match detail {
+                Some(detail) => label.literal(format!(" {detail}"), Category::Detail),
+                None => label,
+            }
+        }
+        CompletionKind::TypeParameter => {
+            let prefix = "_ :: proc($";
+            Label::new(highlights, format!("{prefix}{name}: typeid) {{}}"))
+                .code(prefix.len()..prefix.len() + name.len(), Category::Type)
+        }
+        CompletionKind::File | CompletionKind::Folder => {
+            unplaced_detail = detail;
+            let code = format!("import \"{name}\"");
+            let len = code.len();
+            // Only the path is shown, but the quotes are needed for it to parse as a string
+            Label::new(highlights, code).code("import \"".len()..len - 1, Category::Module)
+        }
+        CompletionKind::Keyword | CompletionKind::Snippet => {
+            unplaced_detail = detail;
+            Label::new(highlights, String::new()).literal(name.clone(), Category::Keyword)
+        }
+        CompletionKind::Operator => {
+            Label::new(highlights, String::new()).literal(name.clone(), Category::Operator)
+        }
+        CompletionKind::Text
+        | CompletionKind::Value
+        | CompletionKind::Unit
+        | CompletionKind::Color
+        | CompletionKind::Reference
+        | CompletionKind::Event
+        | CompletionKind::Other(_) => return None,
+    };
+    let label = match unplaced_detail {
+        Some(detail) if level == CompletionDetail::Full => {
+            label.literal(format!(" {detail}"), Category::Detail)
+        }
+        _ => label,
+    };
+    let label = match package {
+        Some(package) if settings.package_prefix => label.package(package),
+        _ => label,
+    };
+    Some(label.finish(name))
+}
+
+/// Builds a symbol label the same way as [`completion_label`], from the symbol's name alone.
+pub fn symbol_label(symbol: &Symbol, settings: &LabelSettings) -> Option<CodeLabel> {
+    let name = &symbol.name;
+    let highlights = &settings.highlights;
+    let label = match symbol.kind {
+        SymbolKind::Function | SymbolKind::Method | SymbolKind::Constructor => {
+            Label::new(highlights, format!("{name} :: proc() {{}}"))
+                .code(0..name.len(), Category::Function)
+        }
+        SymbolKind::Struct | SymbolKind::Class | SymbolKind::Interface => {
+            Label::new(highlights, format!("{name} :: struct {{}}"))
+                .code(0..name.len(), Category::Type)
+        }
+        SymbolKind::Enum => Label::new(highlights, format!("{name} :: enum {{}}"))
+            .code(0..name.len(), Category::Type),
+        SymbolKind::Field | SymbolKind::Property => {
+            let prefix = "_ :: struct { ";
+            Label::new(highlights, format!("{prefix}{name}: int }}"))
+                .code(prefix.len()..prefix.len() + name.len(), Category::Field)
+        }
+        SymbolKind::Variable => {
+            Label::new(highlights, name.clone()).code(0..name.len(), Category::Variable)
+        }
+        SymbolKind::Module | SymbolKind::Namespace | SymbolKind::Package => {
+            let prefix = "import ";
+            Label::new(highlights, format!("{prefix}{name} \"\""))
+                .code(prefix.len()..prefix.len() + name.len(), Category::Module)
+        }
+        SymbolKind::Constant | SymbolKind::EnumMember => {
+            Label::new(highlights, format!("{name} :: 0")).code(0..name.len(), Category::Constant)
+        }
+        _ => return None,
+    };
+    Some(label.finish(name))
+}
+
+/// ols sometimes repeats the name at the start of the detail (`name :: proc()`, `name: int`).
+fn strip_name<'a>(detail: &'a str, name: &str) -> &'a str {
+    detail
+        .strip_prefix(name)
+        .and_then(|rest| {
+            rest.strip_prefix(" :: ")
+                .or_else(|| rest.strip_prefix(": "))
+        })
+        .unwrap_or(detail)
+        .trim()
+}
+
+/// ols names the package of an imported symbol by qualifying it in the detail
+/// (`fmt.println :: proc(..)`).
+fn package_of<'a>(detail: &'a str, name: &str) -> Option<&'a str> {
+    let (package, rest) = detail.split_once('.')?;
+    let is_identifier = !package.is_empty()
+        && package
+            .chars()
+            .all(|char| char.is_alphanumeric() || char == '_');
+    (is_identifier && rest.starts_with(name)).then_some(package)
+}
+
+/// A code label under construction: the code to highlight and the spans shown from it.
+///
+/// ols details can be anything, so a span that doesn't fit the code doesn't panic; it makes the
+/// label fall back to the plain name instead.
+struct Label<'a> {
+    code: String,
+    spans: Vec<CodeLabelSpan>,
+    highlights: &'a HashMap<String, String>,
+    /// The label's text so far, i.e. what the spans show.
+    text: String,
+    /// Where the name starts in the label's text.
+    name_start: usize,
+    /// Whether every span so far was within the code.
+    valid: bool,
+}
+
+impl<'a> Label<'a> {
+    fn new(highlights: &'a HashMap<String, String>, code: String) -> Self {
+        Self {
+            code,
+            spans: Vec::new(),
+            highlights,
+            text: String::new(),
+            name_start: 0,
+            valid: true,
+        }
+    }
+
+    /// Shows `range` of the code, highlighted by the grammar unless `category` is overridden, in
+    /// which case the text is shown with the override's highlight instead.
+    fn code(mut self, range: Range<usize>, category: Category) -> Self {
+        let Some(text) = self.code.get(range.clone()) else {
+            self.valid = false;
+            return self;
+        };
+        self.text.push_str(text);
+        let span = match self.highlights.get(category.key()) {
+            Some(highlight) => CodeLabelSpan::literal(text, Some(highlight.clone())),
+            None => CodeLabelSpan::code_range(range),
+        };
+        self.spans.push(span);
+        self
+    }
+
+    fn literal(mut self, text: impl Into<String>, category: Category) -> Self {
+        let text = text.into();
+        let highlight = self.literal_highlight(category);
+        self.text.push_str(&text);
+        self.spans.push(CodeLabelSpan::literal(text, highlight));
+        self
+    }
+
+    /// Puts `package.` in front of the label, leaving only the name itself to be filtered on.
+    fn package(mut self, package: &str) -> Self {
+        let highlight = self.literal_highlight(Category::Package);
+        self.spans.splice(
+            0..0,
+            [
+                CodeLabelSpan::literal(package, highlight),
+                CodeLabelSpan::literal(".", None),
+            ],
+        );
+        self.text.insert_str(0, &format!("{package}."));
+        self.name_start = package.len() + 1;
+        self
+    }
+
+    fn literal_highlight(&self, category: Category) -> Option<String> {
+        self.highlights
+            .get(category.key())
+            .cloned()
+            .or_else(|| category.default_highlight().map(str::to_string))
+    }
+
+    /// Every label starts with the item's name, after any package, which is what Zed filters on.
+    /// A label that went wrong, so that the name isn't where it should be, is replaced by the
+    /// unstyled name.
+    fn finish(self, name: &str) -> CodeLabel {
+        let filter_range = self.name_start..self.name_start + name.len();
+        if !self.valid || self.text.get(filter_range.clone()) != Some(name) {
+            return CodeLabel {
+                code: String::new(),
+                spans: vec![CodeLabelSpan::literal(name, None)],
+                filter_range: (0..name.len()).into(),
+            };
+        }
+        CodeLabel {
+            code: self.code,
+            spans: self.spans,
+            filter_range: filter_range.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Span {
+        Code(Range<u32>),
+        Literal(&'static str, Option<&'static str>),
+    }
+
+    /// The parts of `label` that matter to Zed, in a form that can be compared.
+    fn parts(label: &CodeLabel) -> (String, Vec<String>, Range<u32>) {
+        let spans = label
+            .spans
+            .iter()
+            .map(|span| match span {
+                CodeLabelSpan::CodeRange(range) => format!("{:?}", range.start..range.end),
+                CodeLabelSpan::Literal(literal) => {
+                    format!("{:?} {:?}", literal.text, literal.highlight_name)
+                }
+            })
+            .collect();
+        let filter_range = label.filter_range.start..label.filter_range.end;
+        (label.code.clone(), spans, filter_range)
+    }
+
+    fn expected(
+        code: &str,
+        spans: &[Span],
+        filter_range: Range<u32>,
+    ) -> (String, Vec<String>, Range<u32>) {
+        let spans = spans
+            .iter()
+            .map(|span| match span {
+                Span::Code(range) => format!("{range:?}"),
+                Span::Literal(text, highlight) => {
+                    format!("{:?} {:?}", text, highlight.map(str::to_string))
+                }
+            })
+            .collect();
+        (code.to_string(), spans, filter_range)
+    }
+
+    fn label(
+        kind: CompletionKind,
+        name: &str,
+        detail: Option<&str>,
+        settings: &LabelSettings,
+    ) -> Option<(String, Vec<String>, Range<u32>)> {
+        let completion = Completion {
+            label: name.to_string(),
+            detail: detail.map(str::to_string),
+            kind: Some(kind),
+            insert_text_format: None,
+        };
+        completion_label(&completion, settings).map(|label| parts(&label))
+    }
+
+    fn full() -> LabelSettings {
+        LabelSettings {
+            detail: CompletionDetail::Full,
+            ..LabelSettings::default()
+        }
+    }
+
+    #[test]
+    fn procedures_show_their_signature() {
+        let settings = LabelSettings::default();
+        for kind in [
+            CompletionKind::Function,
+            CompletionKind::Method,
+            CompletionKind::Constructor,
+        ] {
+            assert_eq!(
+                label(kind, "println", Some("proc(args: ..any)"), &settings),
+                Some(expected(
+                    "println :: proc(args: ..any) {}",
+                    &[
+                        Span::Code(0..7),
+                        Span::Literal(" ", None),
+                        Span::Code(11..28)
+                    ],
+                    0..7,
+                )),
+            );
+        }
+        // Detail that isn't a signature only shows at the full level
+        assert_eq!(
+            label(CompletionKind::Function, "f", Some("int"), &settings),
+            Some(expected("f :: proc() {}", &[Span::Code(0..1)], 0..1)),
+        );
+        assert_eq!(
+            label(CompletionKind::Function, "f", Some("int"), &full()),
+            Some(expected(
+                "f :: proc() {}",
+                &[Span::Code(0..1), Span::Literal(" int", None)],
+                0..1,
+            )),
+        );
+    }
+
+    #[test]
+    fn package_prefix_comes_before_the_name() {
+        let detail = Some("fmt.println :: proc(args: ..any)");
+        // The package is stripped from the signature either way
+        assert_eq!(
+            label(
+                CompletionKind::Function,
+                "println",
+                detail,
+                &LabelSettings::default()
+            ),
+            Some(expected(
+                "println :: proc(args: ..any) {}",
+                &[
+                    Span::Code(0..7),
+                    Span::Literal(" ", None),
+                    Span::Code(11..28)
+                ],
+                0..7,
+            )),
+        );
+        let settings = LabelSettings {
+            package_prefix: true,
+            ..LabelSettings::default()
+        };
+        assert_eq!(
+            label(CompletionKind::Function, "println", detail, &settings),
+            Some(expected(
+                "println :: proc(args: ..any) {}",
+                &[
+                    Span::Literal("fmt", Some("module")),
+                    Span::Literal(".", None),
+                    Span::Code(0..7),
+                    Span::Literal(" ", None),
+                    Span::Code(11..28),
+                ],
+                4..11,
+            )),
+        );
+    }
+
+    #[test]
+    fn types_and_modules_show_their_name() {
+        let settings = LabelSettings::default();
+        for kind in [
+            CompletionKind::Struct,
+            CompletionKind::Class,
+            CompletionKind::Interface,
+        ] {
+            assert_eq!(
+                label(kind, "Point", Some("struct {x: int}"), &settings),
+                Some(expected("Point :: struct {}", &[Span::Code(0..5)], 0..5)),
+            );
+        }
+        assert_eq!(
+            label(
+                CompletionKind::Struct,
+                "Point",
+                Some("struct {x: int}"),
+                &full()
+            ),
+            Some(expected(
+                "Point :: struct {}",
+                &[Span::Code(0..5), Span::Literal(" struct {x: int}", None)],
+                0..5,
+            )),
+        );
+        assert_eq!(
+            label(CompletionKind::Enum, "Color", None, &settings),
+            Some(expected("Color :: enum {}", &[Span::Code(0..5)], 0..5)),
+        );
+        assert_eq!(
+            label(CompletionKind::Module, "fmt", None, &settings),
+            Some(expected("import fmt \"\"", &[Span::Code(7..10)], 0..3)),
+        );
+        assert_eq!(
+            label(CompletionKind::TypeParameter, "T", None, &settings),
+            Some(expected(
+                "_ :: proc($T: typeid) {}",
+                &[Span::Code(11..12)],
+                0..1
+            )),
+        );
+        for kind in [CompletionKind::File, CompletionKind::Folder] {
+            assert_eq!(
+                label(kind, "core:fmt", None, &settings),
+                Some(expected("import \"core:fmt\"", &[Span::Code(8..16)], 0..8)),
+            );
+        }
+    }
+
+    #[test]
+    fn values_show_their_type() {
+        let settings = LabelSettings::default();
+        for kind in [CompletionKind::Field, CompletionKind::Property] {
+            assert_eq!(
+                label(kind, "x", Some("f32"), &settings),
+                Some(expected(
+                    "_ :: struct { x: f32 }",
+                    &[Span::Code(14..20)],
+                    0..1
+                )),
+            );
+        }
+        // ols sometimes repeats the name in the detail
+        assert_eq!(
+            label(
+                CompletionKind::Variable,
+                "count",
+                Some("count: int"),
+                &settings
+            ),
+            Some(expected("count: int", &[Span::Code(0..10)], 0..5)),
+        );
+        for kind in [CompletionKind::Constant, CompletionKind::EnumMember] {
+            assert_eq!(
+                label(kind, "PI", Some("f64"), &settings),
+                Some(expected(
+                    "PI :: 0",
+                    &[Span::Code(0..2), Span::Literal(" f64", None)],
+                    0..2,
+                )),
+            );
+        }
+    }
+
+    #[test]
+    fn minimal_detail_shows_only_the_name() {
+        let settings = LabelSettings {
+            detail: CompletionDetail::Minimal,
+            ..LabelSettings::default()
+        };
+        assert_eq!(
+            label(
+                CompletionKind::Function,
+                "println",
+                Some("proc()"),
+                &settings
+            ),
+            Some(expected("println :: proc() {}", &[Span::Code(0..7)], 0..7)),
+        );
+        assert_eq!(
+            label(CompletionKind::Field, "x", Some("f32"), &settings),
+            Some(expected("_ :: struct { x }", &[Span::Code(14..15)], 0..1)),
+        );
+        assert_eq!(
+            label(CompletionKind::Variable, "count", Some("int"), &settings),
+            Some(expected("count", &[Span::Code(0..5)], 0..5)),
+        );
+    }
+
+    #[test]
+    fn keywords_and_operators_are_plain_text() {
+        let settings = LabelSettings::default();
+        for kind in [CompletionKind::Keyword, CompletionKind::Snippet] {
+            assert_eq!(
+                label(kind, "return", None, &settings),
+                Some(expected(
+                    "",
+                    &[Span::Literal("return", Some("keyword"))],
+                    0..6
+                )),
+            );
+        }
+        assert_eq!(
+            label(CompletionKind::Operator, "or_else", None, &settings),
+            Some(expected(
+                "",
+                &[Span::Literal("or_else", Some("operator"))],
+                0..7
+            )),
+        );
+    }
+
+    #[test]
+    fn highlight_overrides_replace_the_grammar() {
+        let settings = LabelSettings {
+            highlights: HashMap::from([("function".to_string(), "emphasis".to_string())]),
+            ..LabelSettings::default()
+        };
+        assert_eq!(
+            label(CompletionKind::Function, "main", None, &settings),
+            Some(expected(
+                "main :: proc() {}",
+                &[Span::Literal("main", Some("emphasis"))],
+                0..4,
+            )),
+        );
+    }
+
+    #[test]
+    fn empty_detail_is_ignored() {
+        let settings = full();
+        for detail in ["", "  ", "count: "] {
+            assert_eq!(
+                label(CompletionKind::Variable, "count", Some(detail), &settings),
+                Some(expected("count", &[Span::Code(0..5)], 0..5)),
+                "{detail:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn multibyte_names_use_byte_ranges() {
+        assert_eq!(
+            label(
+                CompletionKind::Variable,
+                "größe",
+                Some("f32"),
+                &LabelSettings::default()
+            ),
+            Some(expected("größe: f32", &[Span::Code(0..12)], 0..7)),
+        );
+    }
+
+    #[test]
+    fn range_splitting_a_char_falls_back_to_the_name() {
+        let highlights = HashMap::new();
+        // `π` is two bytes, so 0..1 is not a char boundary
+        let label = Label::new(&highlights, "π :: 0".to_string())
+            .code(0..1, Category::Constant)
+            .finish("π");
+        assert_eq!(
+            parts(&label),
+            expected("", &[Span::Literal("π", None)], 0..2)
+        );
+        let label = Label::new(&highlights, "π :: 0".to_string())
+            .code(0..100, Category::Constant)
+            .finish("π");
+        assert_eq!(
+            parts(&label),
+            expected("", &[Span::Literal("π", None)], 0..2)
+        );
+    }
+
+    #[test]
+    fn label_not_starting_with_the_name_falls_back() {
+        let highlights = HashMap::new();
+        // A detail placed in front of the name would make Zed filter on the wrong text
+        let label = Label::new(&highlights, "int count".to_string())
+            .code(0..9, Category::Variable)
+            .finish("count");
+        assert_eq!(
+            parts(&label),
+            expected("", &[Span::Literal("count", None)], 0..5)
+        );
+        let label = Label::new(&highlights, "count".to_string())
+            .literal("[]", Category::Detail)
+            .code(0..5, Category::Variable)
+            .finish("count");
+        assert_eq!(
+            parts(&label),
+            expected("", &[Span::Literal("count", None)], 0..5)
+        );
+    }
+
+    #[test]
+    fn kinds_without_a_snippet_get_zed_labels() {
+        let settings = full();
+        for kind in [
+            CompletionKind::Text,
+            CompletionKind::Value,
+            CompletionKind::Unit,
+            CompletionKind::Color,
+            CompletionKind::Reference,
+            CompletionKind::Event,
+            CompletionKind::Other(42),
+        ] {
+            assert_eq!(label(kind, "x", Some("int"), &settings), None);
+        }
+        let completion = Completion {
+            label: "x".to_string(),
+            detail: None,
+            kind: None,
+            insert_text_format: None,
+        };
+        assert!(completion_label(&completion, &settings).is_none());
+    }
+}
@@ -1,186 +1,3276 @@
+mod github;
+mod health;
+mod host;
+mod init_options;
+mod labels;
+mod release_notes;
+mod server;
+mod settings;
+
+use host::{Host, ZedHost};
+use labels::LabelSettings;
+use server::{ArchiveFormat, LanguageServer};
+use settings::{BinarySource, Channel, OdinSettings, UpdateCheckInterval};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use zed::lsp::{Completion, CompletionKind};
+use std::io::{self, Read};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use zed::LanguageServerId;
-use zed::{CodeLabel, CodeLabelSpan};
-use zed_extension_api::{self as zed, settings::LspSettings, Result};
+use zed_extension_api::{
+    self as zed, serde_json,
+    settings::{BinarySettings, LspSettings},
+    Result,
+};
+
+/// File inside a version directory recording when GitHub was last checked for a newer release.
+const LAST_UPDATE_CHECK_FILE: &str = ".last_update_check";
+/// File inside a version directory recording when its binary was last started.
+const LAST_USED_FILE: &str = ".last_used";
+/// File inside a version directory recording the release version it was downloaded from.
+const INSTALLED_VERSION_FILE: &str = ".version";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const BYTES_PER_MB: u64 = 1024 * 1024;
+/// How long to wait before re-fetching a release that is missing our asset, when there is no
+/// installed binary to fall back to. Startup waits on it, so it's kept short.
+const UNPUBLISHED_RELEASE_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// How long to wait before retrying a rate limited request, when there is no installed binary to
+/// fall back to. Only a short secondary limit clears this quickly; longer ones still fail.
+const RATE_LIMIT_RETRY_DELAY: Duration = Duration::from_secs(3);
+/// How many recent releases to list when a pinned release can't be found.
+const RECENT_RELEASE_COUNT: usize = 20;
+/// Shell variables kept with `clean_env`, on top of the configured `env`.
+const CLEAN_ENV_VARS: &[&str] = &["PATH", "ODIN_ROOT"];
+/// File briefly downloaded to check whether GitHub can be reached at all.
+const REACHABILITY_PROBE_FILE: &str = ".reachability_probe";
+/// A tiny GitHub response, fetched to tell a network outage from a failing download.
+const REACHABILITY_PROBE_URL: &str = "https://api.github.com/zen";
+/// File briefly created to check that the cache directory is writable.
+const WRITE_PROBE_FILE: &str = ".write_probe";
+/// File at the worktree root pinning the ols release the project uses.
+const OLS_VERSION_FILE: &str = ".ols-version";
+/// File at the worktree root pinning the Odin release the project is built with.
+const ODIN_VERSION_FILE: &str = ".odin-version";
+/// File in the working directory recording that `clear_cache` has already been acted on.
+const CACHE_CLEARED_FILE: &str = ".cache_cleared";
+/// File in the working directory holding the absolute path of the ols binary last started, so
+/// tasks and scripts can run the same one.
+const ACTIVE_BINARY_FILE: &str = "active-ols-path";
+/// File in the working directory holding the latest `health_check` report.
+const HEALTH_REPORT_FILE: &str = "health-report.txt";
+
+/// File in the cache directory recording the layout scheme of the downloads in it.
+const CACHE_FORMAT_FILE: &str = ".cache_format";
+/// Bump whenever version directory or binary naming changes incompatibly.
+const CACHE_FORMAT_VERSION: &str = "1";
+
+/// Version of `zed_extension_api` the extension is built against; keep in sync with Cargo.toml.
+const ZED_EXTENSION_API_VERSION: &str = "0.0.6";
 
-#[derive(Clone)]
+/// Oldest and newest ols monthly releases the extension has been tested against. Without
+/// `allow_untested_versions` nothing newer is installed, so bump the upper end with every
+/// extension release:
+///
+/// 1. Download the newest ols monthly release and point `lsp.ols.binary.path` at it.
+/// 2. Open an Odin project and check that ols starts, and that diagnostics, completion labels,
+///    hover and go-to-definition work.
+/// 3. Check that `init_options::OLS_OPTIONS` still lists every option the release's `ols.json`
+///    schema has, and add options it introduces to `init_options::OPTION_RELEASES`.
+/// 4. Set the upper end to that release.
+const TESTED_OLS_RELEASES: (&str, &str) = ("dev-2024-04", "dev-2025-09");
+/// How many recent releases to search for the newest tested one, when the latest is untested.
+/// ols publishes about one a month, so this reaches years back.
+const TESTED_RELEASE_SEARCH_COUNT: usize = 100;
+
+/// Where ols is looked for without a `resolution_order`, with `prefer_path` on or nothing
+/// downloaded yet.
+const PATH_FIRST_RESOLUTION_ORDER: &[BinarySource] = &[
+    BinarySource::Settings,
+    BinarySource::Env,
+    BinarySource::VersionManager,
+    BinarySource::Path,
+    BinarySource::Download,
+];
+/// Where ols is looked for without a `resolution_order`, with `prefer_path` off once something
+/// has been downloaded.
+const DOWNLOAD_FIRST_RESOLUTION_ORDER: &[BinarySource] = &[
+    BinarySource::Settings,
+    BinarySource::Env,
+    BinarySource::VersionManager,
+    BinarySource::Download,
+];
+
+#[derive(Debug, Clone)]
 struct OlsBinary {
     path: String,
     args: Option<Vec<String>>,
     environment: Option<Vec<(String, String)>>,
+    /// Release version, when the binary was downloaded by the extension.
+    version: Option<String>,
+    /// Repository the binary's release was looked up in, when it was on this resolution.
+    repository: Option<String>,
+    /// Where the binary was found.
+    source: BinarySource,
 }
 
 struct OdinExtension {
-    cached_binary_path: Option<String>,
+    /// Downloaded binary paths, keyed by language server so separate instances don't interfere.
+    cached_binary_paths: HashMap<String, String>,
+    /// Label settings of each started language server; labels are requested without a worktree,
+    /// so they can't load settings themselves.
+    label_settings: HashMap<LanguageServerId, LabelSettings>,
+    /// `dev-YYYY-MM` release of each started language server's ols, where it's known, for
+    /// checking the initialization options sent to it.
+    ols_releases: HashMap<LanguageServerId, String>,
+    /// Binaries on PATH whose release couldn't be checked against `path_min_version`, so the
+    /// warning about each is only logged once.
+    unversioned_path_binaries: HashSet<String>,
+    /// Untested releases already reported as skipped or outside the tested range, so each is
+    /// only logged once.
+    reported_untested_releases: HashSet<String>,
 }
 
 impl OdinExtension {
     fn language_server_binary(
         &mut self,
-        language_server_id: &LanguageServerId,
-        worktree: &zed::Worktree,
+        host: &impl Host,
+        language_server_id: &str,
+        lsp_settings: LspSettings,
+        settings: &OdinSettings,
     ) -> Result<OlsBinary> {
-        let mut args: Option<Vec<String>> = None;
+        let environment = ols_environment(host, settings);
 
-        // Get environment based on current platform
-        let (platform, arch) = zed::current_platform();
-        let environment = match platform {
-            zed::Os::Mac | zed::Os::Linux => Some(worktree.shell_env()),
-            zed::Os::Windows => None,
+        // A project that pins its Odin release doesn't get an ols for a different toolchain
+        if let Some(pinned_odin) = pinned_odin_version(host, settings) {
+            match detect_odin_version(host, environment.as_deref()) {
+                Some(odin_version) if odin_version != pinned_odin => {
+                    return Err(format!(
+                        "Odin {pinned_odin} is pinned but Odin {odin_version} was found; install \
+                         Odin {pinned_odin} or update odin_version / {ODIN_VERSION_FILE}"
+                    ));
+                }
+                Some(_) => {}
+                None => log(&format!(
+                    "warning: Odin {pinned_odin} is pinned but the installed Odin release could \
+                     not be detected"
+                )),
+            }
+        }
+
+        warn_unsupported_settings(settings.cache_dir.as_deref());
+        self.prepare_cache(language_server_id, settings)?;
+        let resolution = Resolution::new(host, settings, lsp_settings.binary, environment);
+        if let Some(binary) = self.find_binary(&resolution, language_server_id)? {
+            return Ok(binary);
+        }
+
+        let (release, repository) =
+            match resolution.select_release(&mut self.reported_untested_releases)? {
+                ReleaseChoice::Download(release, repository) => (release, repository),
+                ReleaseChoice::Keep { binary, remember } => {
+                    if remember {
+                        self.cached_binary_paths
+                            .insert(language_server_id.to_string(), binary.path.clone());
+                    }
+                    return Ok(binary);
+                }
+            };
+        let (version_dir, binary_path) = match resolution.install(&release, &repository)? {
+            Install::Release {
+                version_dir_name,
+                version_dir,
+                binary_path,
+                downloaded,
+            } => {
+                if downloaded {
+                    resolution.clean_up(&version_dir_name, &version_dir)?;
+                }
+                (version_dir, binary_path)
+            }
+            Install::Previous(binary) => {
+                self.cached_binary_paths
+                    .insert(language_server_id.to_string(), binary.path.clone());
+                return Ok(binary);
+            }
         };
+        record_installed_version(&version_dir, &release.version);
+        record_update_check(&version_dir);
 
-        // LSP settings specified for ols
-        if let Ok(lsp_settings) = LspSettings::for_worktree("ols", worktree) {
-            if let Some(binary) = lsp_settings.binary {
-                args = binary.arguments;
-                if let Some(path) = binary.path {
-                    return Ok(OlsBinary {
-                        path: path.clone(),
-                        args,
-                        environment,
-                    });
+        // Set the cached binary path and return it.
+        self.cached_binary_paths
+            .insert(language_server_id.to_string(), binary_path.clone());
+        Ok(OlsBinary {
+            version: Some(release.version),
+            repository: Some(repository),
+            ..resolution.binary(binary_path, None, BinarySource::Download)
+        })
+    }
+
+    /// Clears the download cache when clear_cache asks for it, and migrates one laid out by an
+    /// older version of the extension, before anything in it is looked up.
+    fn prepare_cache(&mut self, language_server_id: &str, settings: &OdinSettings) -> Result<()> {
+        let server = settings.server.implementation();
+        let cache_dir = settings.cache_dir.as_deref();
+
+        // Clear downloaded binaries once per time the clear_cache setting is switched on
+        if settings.clear_cache && !settings.manage_cache {
+            log("warning: ignoring clear_cache because manage_cache is off");
+        } else if settings.clear_cache {
+            if fs::metadata(CACHE_CLEARED_FILE).is_err() {
+                clear_download_cache(cache_dir, server)?;
+                trace(settings, "cleared downloaded binaries for clear_cache");
+                self.cached_binary_paths.remove(language_server_id);
+                fs::write(CACHE_CLEARED_FILE, "")
+                    .map_err(|err| format!("failed to write '{CACHE_CLEARED_FILE}': {err}"))?;
+            }
+        } else {
+            fs::remove_file(CACHE_CLEARED_FILE).ok();
+        }
+
+        // Downloads laid out by an older naming scheme can't be found reliably, so start over.
+        // A cache without a marker predates it: its downloads use the first scheme, except for
+        // those made before versions were recorded, which are replaced.
+        let cache_format_file = cache_path(cache_dir, CACHE_FORMAT_FILE);
+        match fs::read_to_string(&cache_format_file) {
+            Ok(format) if format.trim() == CACHE_FORMAT_VERSION => {}
+            Ok(format) if settings.manage_cache => {
+                log(&format!(
+                    "cache format {} is outdated, removing downloads made with it",
+                    format.trim()
+                ));
+                clear_download_cache(cache_dir, server)?;
+                self.cached_binary_paths.remove(language_server_id);
+                write_state(&cache_format_file, CACHE_FORMAT_VERSION)
+                    .map_err(|err| format!("failed to write '{cache_format_file}': {err}"))?;
+            }
+            Ok(format) => log(&format!(
+                "warning: cache format {} is outdated but manage_cache is off; older downloads \
+                 may not be found",
+                format.trim()
+            )),
+            Err(_) => {
+                if settings.manage_cache {
+                    remove_unrecorded_versions(cache_dir, server);
+                    self.cached_binary_paths.remove(language_server_id);
                 }
+                write_state(&cache_format_file, CACHE_FORMAT_VERSION).ok();
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks the binary up in each source of the resolution order that doesn't need GitHub.
+    /// `None` means a release has to be looked up, to download or to check for an update.
+    fn find_binary(
+        &mut self,
+        resolution: &Resolution<impl Host>,
+        language_server_id: &str,
+    ) -> Result<Option<OlsBinary>> {
+        let Resolution {
+            host,
+            settings,
+            server,
+            ..
+        } = *resolution;
+        let pinned_version = &resolution.pinned_version;
+        let installed_binary = &resolution.installed_binary;
+
+        // With several ols around, precedence can pick one the user didn't expect, so they can
+        // have every candidate listed; the one started is logged as it starts
+        if settings.list_candidates {
+            let sources = [
+                ("binary.path", resolution.settings_binary.clone()),
+                (
+                    settings.binary_env_var.as_str(),
+                    resolution.env_binary.clone(),
+                ),
+                (
+                    "version_manager",
+                    resolution
+                        .managed_binary
+                        .as_ref()
+                        .map(|(path, _)| path.clone()),
+                ),
+                (
+                    "PATH",
+                    resolution.path_binary(&mut self.unversioned_path_binaries),
+                ),
+            ];
+            let candidates: Vec<_> = sources
+                .into_iter()
+                .filter_map(|(source, path)| Some(format!("{} ({source})", path?)))
+                .chain(
+                    resolution
+                        .installed
+                        .iter()
+                        .map(|(_, binary_path)| format!("{binary_path} (downloaded)")),
+                )
+                .collect();
+            if candidates.len() > 1 {
+                log(&format!(
+                    "found {} candidates for {}:\n  {}",
+                    candidates.len(),
+                    server.name(),
+                    candidates.join("\n  ")
+                ));
             }
         }
 
-        // Found ols in worktree, return it
-        if let Some(path) = worktree.which("ols") {
-            self.cached_binary_path = Some(path.clone());
-            return Ok(OlsBinary {
-                path,
-                args,
-                environment,
+        let cached_binary = self
+            .cached_binary_paths
+            .get(language_server_id)
+            .filter(|path| fs::metadata(path).is_ok_and(|stat| stat.is_file()))
+            .map(|path| {
+                let version = Path::new(path)
+                    .parent()
+                    .and_then(|dir| installed_version(&dir.to_string_lossy()));
+                (path.clone(), version)
+            })
+            .filter(|(_, version)| pinned_version.is_none() || version == pinned_version);
+
+        // Without a resolution_order, PATH comes before downloads unless prefer_path is off and
+        // there is a download to use. The PATH binary is deliberately not cached, so it is looked
+        // up again on every resolution.
+        let prefer_path = settings.prefer_path
+            || (!self.cached_binary_paths.contains_key(language_server_id)
+                && installed_binary.is_none());
+        let resolution_order = settings
+            .resolution_order
+            .as_deref()
+            .unwrap_or(if prefer_path {
+                PATH_FIRST_RESOLUTION_ORDER
+            } else {
+                DOWNLOAD_FIRST_RESOLUTION_ORDER
             });
+        // Switching between versions that are already downloaded never touches the network. An
+        // installed version picked this way stands in for the first source that would look for
+        // one, so only sources the user pointed at explicitly come before it.
+        let mut use_installed_version = settings.use_installed_version.as_ref();
+        for &source in resolution_order {
+            if matches!(
+                source,
+                BinarySource::Path | BinarySource::Cache | BinarySource::Download
+            ) {
+                if let Some(selected) = use_installed_version.take() {
+                    let installed: Vec<_> = resolution
+                        .installed
+                        .iter()
+                        .filter_map(|(version_dir, binary_path)| {
+                            Some((installed_version(version_dir)?, binary_path.clone()))
+                        })
+                        .collect();
+                    match installed.iter().find(|(version, _)| version == selected) {
+                        Some((version, binary_path)) => {
+                            resolution.trace(format!(
+                                "using installed {binary_path} from use_installed_version"
+                            ));
+                            host.make_file_executable(binary_path)?;
+                            return Ok(Some(resolution.binary(
+                                binary_path.clone(),
+                                Some(version.clone()),
+                                BinarySource::Cache,
+                            )));
+                        }
+                        None => log(&format!(
+                            "warning: use_installed_version {selected} is not installed \
+                             (installed: {:?}); resolving {} as usual",
+                            installed
+                                .iter()
+                                .map(|(version, _)| version)
+                                .collect::<Vec<_>>(),
+                            server.name(),
+                        )),
+                    }
+                }
+            }
+            let found = match source {
+                BinarySource::Settings => {
+                    resolution.settings_binary.clone().map(|path| (path, None))
+                }
+                BinarySource::Env => resolution.env_binary.clone().map(|path| (path, None)),
+                BinarySource::VersionManager => resolution
+                    .managed_binary
+                    .as_ref()
+                    .map(|(path, _)| (path.clone(), None)),
+                BinarySource::Path if pinned_version.is_none() => resolution
+                    .path_binary(&mut self.unversioned_path_binaries)
+                    .map(|path| (path, None)),
+                BinarySource::Path => None,
+                BinarySource::Cache => cached_binary.clone().or_else(|| {
+                    installed_binary.as_ref().map(|(version_dir, binary_path)| {
+                        (binary_path.clone(), installed_version(version_dir))
+                    })
+                }),
+                BinarySource::Download => break,
+            };
+            let Some((path, version)) = found else {
+                continue;
+            };
+            resolution.trace(format!("using {path} from {}", source.describe()));
+            if source == BinarySource::Cache {
+                // The executable bit may have been lost since it was set, e.g. by a backup
+                // restore
+                host.make_file_executable(&path)?;
+                self.cached_binary_paths
+                    .insert(language_server_id.to_string(), path.clone());
+            }
+            let binary = resolution.binary(path, version, source);
+            return Ok(Some(match (source, &resolution.managed_binary) {
+                (BinarySource::VersionManager, Some((_, args))) => OlsBinary {
+                    args: args.clone(),
+                    ..binary
+                },
+                _ => binary,
+            }));
+        }
+        if !resolution_order.contains(&BinarySource::Download) {
+            return Err(format!(
+                "no {} found from any source in resolution_order {resolution_order:?}",
+                server.name()
+            ));
+        }
+
+        // A binary downloaded or checked earlier this session is used again without another
+        // update check
+        if let Some((path, version)) = cached_binary {
+            host.make_file_executable(&path)?;
+            resolution.trace(format!("using cached {path}"));
+            return Ok(Some(resolution.binary(path, version, BinarySource::Cache)));
+        }
+
+        // Previously downloaded binary found, return it unless an update check is due. A pinned
+        // version is never updated.
+        if let Some((version_dir, binary_path)) = installed_binary {
+            if pinned_version.is_some()
+                || !update_check_due(settings.update_check_interval, version_dir)
+            {
+                host.make_file_executable(binary_path)?;
+                resolution.trace(format!(
+                    "using downloaded {binary_path}, no update check due"
+                ));
+                self.cached_binary_paths
+                    .insert(language_server_id.to_string(), binary_path.clone());
+                return Ok(Some(resolution.binary(
+                    binary_path.clone(),
+                    installed_version(version_dir),
+                    BinarySource::Cache,
+                )));
+            }
         }
 
-        // Binary location cached, return it
-        if let Some(path) = &self.cached_binary_path {
-            if fs::metadata(path).map_or(false, |stat| stat.is_file()) {
-                return Ok(OlsBinary {
-                    path: path.clone(),
-                    args,
-                    environment,
+        // Downloads wait until the user allows them, e.g. off a metered connection
+        if !settings.download_on_startup {
+            let Some((version_dir, binary_path)) = installed_binary else {
+                return Err(format!(
+                    "no {name} is installed and download_on_startup is off; set it to true to \
+                     download {name}, or install it yourself and set lsp.ols.binary.path",
+                    name = server.name(),
+                ));
+            };
+            resolution.trace(format!("using {binary_path}, download_on_startup is off"));
+            host.make_file_executable(binary_path)?;
+            return Ok(Some(resolution.binary(
+                binary_path.clone(),
+                installed_version(version_dir),
+                BinarySource::Cache,
+            )));
+        }
+        Ok(None)
+    }
+}
+
+/// What resolving the binary of one language server start works from: the settings, and what
+/// was found on the machine before anything is downloaded.
+struct Resolution<'a, H: Host> {
+    host: &'a H,
+    settings: &'a OdinSettings,
+    server: &'static dyn LanguageServer,
+    platform: zed::Os,
+    arch: zed::Architecture,
+    cache_dir: Option<&'a str>,
+    args: Option<Vec<String>>,
+    environment: Option<Vec<(String, String)>>,
+    /// Binary from lsp.ols.binary.path.
+    settings_binary: Option<String>,
+    /// Binary from the `binary_env_var` environment variable.
+    env_binary: Option<String>,
+    /// Version manager to run ols through, and the arguments that do.
+    managed_binary: Option<(String, Option<Vec<String>>)>,
+    pinned_version: Option<String>,
+    binary_names: Vec<String>,
+    asset_stems: Vec<String>,
+    /// Downloaded versions as `(version_dir, binary_path)`, walked once per resolution, which
+    /// matters where stat calls are slow (network filesystems).
+    installed: Vec<(String, String)>,
+    /// The installed version to use: the pinned one, or else the most recent.
+    installed_binary: Option<(String, String)>,
+}
+
+/// What `Resolution::select_release` settled on.
+enum ReleaseChoice {
+    /// A release to install, and the repository it came from.
+    Download(zed::GithubRelease, String),
+    /// An installed binary to keep instead, and whether it is good for the rest of the session.
+    Keep { binary: OlsBinary, remember: bool },
+}
+
+/// What `Resolution::install` left in the cache.
+enum Install {
+    /// The release's binary, and whether it was downloaded just now rather than already there.
+    Release {
+        version_dir_name: String,
+        version_dir: String,
+        binary_path: String,
+        downloaded: bool,
+    },
+    /// The download was rejected, so the previously downloaded binary is used.
+    Previous(OlsBinary),
+}
+
+impl<'a, H: Host> Resolution<'a, H> {
+    fn new(
+        host: &'a H,
+        settings: &'a OdinSettings,
+        binary: Option<BinarySettings>,
+        environment: Option<Vec<(String, String)>>,
+    ) -> Self {
+        let server = settings.server.implementation();
+        let cache_dir = settings.cache_dir.as_deref();
+        let (platform, arch) = host.current_platform();
+        trace(settings, &format!("cache directory {cache_dir:?}"));
+
+        // LSP settings specified for ols
+        let (args, settings_binary) = match binary {
+            Some(binary) => (binary.arguments, binary.path),
+            None => (None, None),
+        };
+
+        // Tooling managed through the environment (direnv, nix shells) can point at its own ols
+        let env_binary =
+            environment
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .find_map(|(key, value)| {
+                    (key == &settings.binary_env_var && !value.is_empty()).then(|| value.clone())
                 });
+
+        // Version managers know which ols the project selected; running it through them uses that
+        // one even where their shims aren't on PATH
+        let path_name = settings.binary_name.as_deref().unwrap_or(server.name());
+        let managed_binary = settings.version_manager.and_then(|manager| {
+            let Some(path) = host.which(manager.command()) else {
+                log(&format!(
+                    "warning: version_manager {} is not on PATH",
+                    manager.command()
+                ));
+                return None;
+            };
+            let args = manager
+                .exec_args(path_name)
+                .into_iter()
+                .chain(args.clone().unwrap_or_default())
+                .collect();
+            Some((path, Some(args)))
+        });
+
+        // A pinned version only ever resolves to that release, downloaded by the extension
+        let pinned_version = pinned_version(host, settings);
+        let binary_names = server.binary_names(platform, arch);
+        let installed = installed_binaries(cache_dir, server, &binary_names, platform, arch);
+        let installed_binary = match &pinned_version {
+            Some(pinned_version) => installed
+                .iter()
+                .find(|(version_dir, _)| {
+                    installed_version(version_dir).as_ref() == Some(pinned_version)
+                })
+                .cloned(),
+            None => installed.first().cloned(),
+        };
+        trace(
+            settings,
+            &format!("pinned version {pinned_version:?}, installed binary {installed_binary:?}"),
+        );
+
+        Self {
+            host,
+            settings,
+            server,
+            platform,
+            arch,
+            cache_dir,
+            args,
+            environment,
+            settings_binary,
+            env_binary,
+            managed_binary,
+            pinned_version,
+            binary_names,
+            asset_stems: server.asset_stems(platform, arch),
+            installed,
+            installed_binary,
+        }
+    }
+
+    /// The binary at `path`, started with this resolution's arguments and environment.
+    fn binary(&self, path: String, version: Option<String>, source: BinarySource) -> OlsBinary {
+        OlsBinary {
+            path,
+            args: self.args.clone(),
+            environment: self.environment.clone(),
+            version,
+            repository: None,
+            source,
+        }
+    }
+
+    fn trace(&self, message: String) {
+        trace(self.settings, &message);
+    }
+
+    /// Finds the release asset for this platform, preferring the configured or usual archive
+    /// format if the release lists several.
+    fn find_asset<'r>(
+        &self,
+        release: &'r zed::GithubRelease,
+    ) -> Option<(&'r zed::GithubReleaseAsset, ArchiveFormat)> {
+        find_asset(
+            release,
+            &self.asset_stems,
+            self.settings.archive_format(self.platform),
+            self.server.archive_format(self.platform),
+        )
+    }
+
+    /// The name of the asset `find_asset` looks for first.
+    fn asset_name(&self) -> String {
+        self.settings
+            .archive_format(self.platform)
+            .unwrap_or(self.server.archive_format(self.platform))
+            .asset_name(&self.asset_stems[0])
+    }
+
+    /// Looks ols up on PATH. Binaries whose release can't be checked against path_min_version
+    /// are added to `unversioned`, so each is only warned about once.
+    fn path_binary(&self, unversioned: &mut HashSet<String>) -> Option<String> {
+        let Self {
+            host,
+            settings,
+            server,
+            platform,
+            ..
+        } = *self;
+        // Installs under another name, e.g. one of several side by side, are found by binary_name
+        let path_name = settings.binary_name.as_deref().unwrap_or(server.name());
+        let path = host.which(path_name).or_else(|| match platform {
+            zed::Os::Windows if !path_name.ends_with(".exe") => {
+                host.which(&format!("{path_name}.exe"))
+            }
+            _ => None,
+        })?;
+        // A stale manual install shouldn't shadow a good download. ols can't be run from here, so
+        // its release is only known when the install path names it.
+        let Some(min_version) = &settings.path_min_version else {
+            return Some(path);
+        };
+        match dev_release(&path) {
+            Some(version) if version < min_version.as_str() => {
+                log(&format!(
+                    "skipping {path} on PATH: release {version} is older than path_min_version \
+                     {min_version}"
+                ));
+                None
+            }
+            Some(_) => Some(path),
+            None => {
+                if unversioned.insert(path.clone()) {
+                    log(&format!(
+                        "warning: the release of {path} on PATH is unknown, so it can't be \
+                         checked against path_min_version; using it anyway"
+                    ));
+                }
+                Some(path)
             }
         }
+    }
+
+    /// Picks the release to install: the pinned one, the one matching the detected Odin or the
+    /// latest, held back to a tested release unless allow_untested_versions is on. An installed
+    /// binary is kept instead when it is already that release or GitHub can't be used. Untested
+    /// releases are logged the first time they're added to `reported_untested_releases`.
+    fn select_release(
+        &self,
+        reported_untested_releases: &mut HashSet<String>,
+    ) -> Result<ReleaseChoice> {
+        let Self {
+            host,
+            settings,
+            server,
+            cache_dir,
+            ..
+        } = *self;
+        let pinned_version = &self.pinned_version;
+        let installed_binary = &self.installed_binary;
+
+        // Downloading needs a writable cache; without one (e.g. some remote setups) say so up front
+        // rather than failing later with IO errors
+        ensure_cache_writable(cache_dir).map_err(|err| {
+            format!(
+                "automatic download of {name} is not available in this environment: {err}. \
+                 Install {name} on PATH or set lsp.ols.binary.path",
+                name = server.name(),
+            )
+        })?;
 
         // Update installation status to "Checking for Update"
-        zed::set_language_server_installation_status(
-            &language_server_id,
-            &zed::LanguageServerInstallationStatus::CheckingForUpdate,
-        );
+        host.set_installation_status(&zed::LanguageServerInstallationStatus::CheckingForUpdate);
 
-        // Download the latest github release
-        let release = zed::latest_github_release(
-            "DanielGavin/ols",
-            zed::GithubReleaseOptions {
-                require_assets: true,
-                pre_release: true,
-            },
-        )?;
+        let asset_name = self.asset_name();
+        let has_asset = |release: &zed::GithubRelease| self.find_asset(release).is_some();
 
-        // Set the asset name's format based on the current arch and platform
-        let asset_name = format!(
-            "ols-{arch}-{os}.{extension}",
-            arch = match arch {
-                zed::Architecture::Aarch64 => "arm64",
-                zed::Architecture::X86 => "x86",
-                zed::Architecture::X8664 => "x86_64",
+        // Download the latest github release from the selected channel. GitHub doesn't tell us
+        // whether a release is a pre-release, so the pre-release channel means "newest of any".
+        // Each candidate repository is tried in turn until one has our asset.
+        // Each fetch returns the release along with the repository it came from, since that's
+        // where its assets, notes and hints are
+        let repositories = release_repositories(settings);
+        let fetch_release = |channel: Channel| {
+            let mut fallback = None;
+            for repository in &repositories {
+                let release = retry_github_request(installed_binary.is_none(), || {
+                    host.latest_github_release(
+                        repository,
+                        zed::GithubReleaseOptions {
+                            require_assets: true,
+                            pre_release: channel == Channel::Prerelease,
+                        },
+                    )
+                });
+                // GitHub's latest pre-release is the most recently created one, which isn't
+                // always the newest version, so recent releases are ranked by semver precedence
+                let release = match release {
+                    Ok(latest) if channel == Channel::Prerelease => {
+                        let recent = github::list_releases(host, repository, RECENT_RELEASE_COUNT)
+                            .unwrap_or_else(|err| {
+                                self.trace(format!("failed to list {repository} releases: {err}"));
+                                Vec::new()
+                            });
+                        let newest = recent
+                            .into_iter()
+                            .map(|listed| listed.release)
+                            .filter(has_asset)
+                            .fold(latest, |newest, release| {
+                                match semver_cmp(&release.version, &newest.version) {
+                                    Some(Ordering::Greater) => release,
+                                    _ => newest,
+                                }
+                            });
+                        Ok(newest)
+                    }
+                    release => release,
+                };
+                match release {
+                    Ok(release) if has_asset(&release) => {
+                        if repositories.len() > 1 {
+                            log(&format!(
+                                "using release {} from {repository}",
+                                release.version
+                            ));
+                        }
+                        // require_assets skips releases whose assets aren't uploaded yet, so the
+                        // newest tag may not be the one we got; say so rather than leave users
+                        // thinking they're on it
+                        let newest = host.latest_github_release(
+                            repository,
+                            zed::GithubReleaseOptions {
+                                require_assets: false,
+                                pre_release: channel == Channel::Prerelease,
+                            },
+                        );
+                        if let Ok(newest) = newest {
+                            if newest.version != release.version {
+                                log(&format!(
+                                    "{repository} release {} has no assets yet, using the \
+                                     previous release {}",
+                                    newest.version, release.version,
+                                ));
+                            }
+                        }
+                        return Ok((release, repository.clone()));
+                    }
+                    Ok(release) => {
+                        self.trace(format!(
+                            "{repository} release {} lacks our asset",
+                            release.version
+                        ));
+                        if !matches!(fallback, Some(Ok(_))) {
+                            fallback = Some(Ok((release, repository.clone())));
+                        }
+                    }
+                    Err(err) => {
+                        self.trace(format!("{repository}: {err}"));
+                        if !matches!(fallback, Some(Ok(_))) {
+                            let err = if is_rate_limited(&err) {
+                                rate_limit_error(&err)
+                            } else {
+                                err
+                            };
+                            fallback = Some(Err(format!("{repository}: {err}")));
+                        }
+                    }
+                }
+            }
+            fallback.unwrap_or_else(|| Err("no repositories to download ols from".to_string()))
+        };
+        // A specific release is looked up by its tag instead, in the first repository that has
+        // our asset for it
+        let fetch_tagged_release = |tag: &str| {
+            let mut fallback = None;
+            for repository in &repositories {
+                match retry_github_request(installed_binary.is_none(), || {
+                    github::release_by_tag(host, repository, tag)
+                }) {
+                    Ok(release) if has_asset(&release) => {
+                        if repositories.len() > 1 {
+                            log(&format!("using release {tag} from {repository}"));
+                        }
+                        return Ok((release, repository.clone()));
+                    }
+                    Ok(release) => {
+                        self.trace(format!("{repository} release {tag} lacks our asset"));
+                        if !matches!(fallback, Some(Ok(_))) {
+                            fallback = Some(Ok((release, repository.clone())));
+                        }
+                    }
+                    Err(err) => {
+                        self.trace(format!("{repository}: {err}"));
+                        if !matches!(fallback, Some(Ok(_))) {
+                            fallback = Some(Err(format!("{repository}: {err}")));
+                        }
+                    }
+                }
+            }
+            fallback.unwrap_or_else(|| Err("no repositories to download ols from".to_string()))
+        };
+        // ols has to match the Odin compiler, so without a pin the ols release named after the
+        // detected Odin release is preferred, and the latest one only used if it has no asset
+        let odin_release = match &pinned_version {
+            Some(_) => None,
+            None => detect_odin_version(host, self.environment.as_deref()),
+        };
+        let fetch_wanted_release = || match (&pinned_version, &odin_release) {
+            (Some(pinned_version), _) => fetch_tagged_release(pinned_version),
+            (None, Some(odin_release)) => match fetch_tagged_release(odin_release) {
+                Ok((release, repository)) if has_asset(&release) => Ok((release, repository)),
+                result => {
+                    let reason = match result {
+                        Ok(_) => format!("has no {asset_name} asset"),
+                        Err(err) => format!("could not be fetched ({err})"),
+                    };
+                    log(&format!(
+                        "warning: the ols release matching Odin {odin_release} {reason}; using \
+                         the latest, which may not be compatible. If ols misbehaves, install a \
+                         matching ols and set lsp.ols.binary.path"
+                    ));
+                    fetch_release(settings.channel)
+                }
             },
-            os = match platform {
-                zed::Os::Mac => "darwin",
-                zed::Os::Linux => "unknown-linux-gnu",
-                zed::Os::Windows => "pc-windows-msvc",
+            (None, None) => fetch_release(settings.channel),
+        };
+        let (mut release, mut repository) = match fetch_wanted_release() {
+            Ok(fetched) => fetched,
+            Err(err) if is_malformed_response(&err) || is_rate_limited(&err) => {
+                let explanation = if is_rate_limited(&err) {
+                    err
+                } else {
+                    format!(
+                        "GitHub's release response was not what was expected, which usually \
+                         means a proxy is interfering ({err})"
+                    )
+                };
+                let Some((version_dir, binary_path)) = installed_binary else {
+                    return Err(explanation);
+                };
+                log(&format!("warning: {explanation}; keeping {binary_path}"));
+                host.make_file_executable(binary_path)?;
+                return Ok(ReleaseChoice::Keep {
+                    binary: self.binary(
+                        binary_path.clone(),
+                        installed_version(version_dir),
+                        BinarySource::Cache,
+                    ),
+                    remember: false,
+                });
+            }
+            Err(err) if settings.check_connectivity && !github_reachable(host) => {
+                return Err(format!("no network connectivity to GitHub detected: {err}"));
+            }
+            // A pin that can't be found is usually a mistyped or unpublished tag, so list what
+            // there is to pin instead
+            Err(err) if pinned_version.is_some() => {
+                let recent = |repository: &str| {
+                    github::list_releases(host, repository, RECENT_RELEASE_COUNT)
+                        .map(|releases| {
+                            releases
+                                .into_iter()
+                                .map(|listed| {
+                                    if listed.pre_release {
+                                        format!("{} (pre-release)", listed.release.version)
+                                    } else {
+                                        listed.release.version
+                                    }
+                                })
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        })
+                        .unwrap_or_else(|err| format!("unavailable ({err})"))
+                };
+                // The tag was missing from every repository, so each one's releases are candidates
+                let recent = match repositories.as_slice() {
+                    [repository] => recent(repository),
+                    repositories => repositories
+                        .iter()
+                        .map(|repository| format!("{repository}: {}", recent(repository)))
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                };
+                return Err(format!("{err}; recent releases: {recent}"));
+            }
+            Err(err) => return Err(err),
+        };
+        self.trace(format!(
+            "{} release is {} from {repository} with assets {:?}",
+            match (&pinned_version, &odin_release) {
+                (Some(_), _) => "pinned".to_string(),
+                (None, Some(odin_release)) if *odin_release == release.version => {
+                    format!("Odin {odin_release} compatible")
+                }
+                _ => format!("latest {:?}", settings.channel),
             },
-            extension = match platform {
-                zed::Os::Mac | zed::Os::Linux => "zip",
-                zed::Os::Windows => "zip",
+            release.version,
+            release
+                .assets
+                .iter()
+                .map(|asset| &asset.name)
+                .collect::<Vec<_>>(),
+        ));
+        // Stay on a tested release rather than moving to one we haven't tried yet, unless that
+        // very release was asked for or is the one matching Odin
+        if !settings.allow_untested_versions
+            && pinned_version.is_none()
+            && odin_release.as_ref() != Some(&release.version)
+            && !is_tested_release(&release.version)
+        {
+            if let Some((version_dir, binary_path)) = &installed_binary {
+                let version = installed_version(version_dir);
+                if version.as_deref().is_some_and(is_tested_release) {
+                    record_update_check(version_dir);
+                    host.make_file_executable(binary_path)?;
+                    if reported_untested_releases.insert(release.version.clone()) {
+                        log(&format!(
+                            "not updating to untested {} {}; set allow_untested_versions to \
+                             update",
+                            server.name(),
+                            release.version,
+                        ));
+                    }
+                    return Ok(ReleaseChoice::Keep {
+                        binary: self.binary(binary_path.clone(), version, BinarySource::Cache),
+                        remember: true,
+                    });
+                }
             }
-        );
+            // Nothing tested is installed, so install the newest tested release instead
+            let tested = github::list_releases(host, &repository, TESTED_RELEASE_SEARCH_COUNT)
+                .unwrap_or_else(|err| {
+                    self.trace(format!("failed to list {repository} releases: {err}"));
+                    Vec::new()
+                })
+                .into_iter()
+                .filter(|listed| settings.channel == Channel::Prerelease || !listed.pre_release)
+                .map(|listed| listed.release)
+                .find(|candidate| is_tested_release(&candidate.version) && has_asset(candidate));
+            let first_report = reported_untested_releases.insert(release.version.clone());
+            match tested {
+                Some(tested) => {
+                    if first_report {
+                        log(&format!(
+                            "{name} {} is untested, installing {}, the newest tested release; set \
+                             allow_untested_versions to use {name} {0}",
+                            release.version,
+                            tested.version,
+                            name = server.name(),
+                        ));
+                    }
+                    release = tested;
+                }
+                None if first_report => log(&format!(
+                    "warning: {} {} is outside the tested range {} to {}, and {repository} has \
+                     no tested release for this platform",
+                    server.name(),
+                    release.version,
+                    TESTED_OLS_RELEASES.0,
+                    TESTED_OLS_RELEASES.1,
+                )),
+                None => {}
+            }
+        }
+
+        // Latest release already installed, return it without touching the filesystem further
+        if let Some((version_dir, binary_path)) = &installed_binary {
+            if installed_version(version_dir).as_deref() == Some(release.version.as_str()) {
+                record_update_check(version_dir);
+                host.make_file_executable(binary_path)?;
+                self.trace(format!("{binary_path} is already the latest release"));
+                let binary = OlsBinary {
+                    repository: Some(repository),
+                    ..self.binary(
+                        binary_path.clone(),
+                        Some(release.version),
+                        BinarySource::Cache,
+                    )
+                };
+                return Ok(ReleaseChoice::Keep {
+                    binary,
+                    remember: true,
+                });
+            }
+        }
+
+        // A release that is still being published may not have our asset yet; with nothing
+        // installed to fall back to, give it one more chance.
+        if !has_asset(&release) && installed_binary.is_none() {
+            log(&format!(
+                "release {} has no {asset_name} asset yet, retrying in {} seconds",
+                release.version,
+                UNPUBLISHED_RELEASE_RETRY_DELAY.as_secs(),
+            ));
+            host.set_installation_status(&zed::LanguageServerInstallationStatus::CheckingForUpdate);
+            thread::sleep(UNPUBLISHED_RELEASE_RETRY_DELAY);
+            (release, repository) = fetch_wanted_release()?;
+        }
+        if !has_asset(&release)
+            && pinned_version.is_none()
+            && settings.channel == Channel::Stable
+            && settings.channel_fallback
+        {
+            log(&format!(
+                "stable release {} has no {asset_name} asset, falling back to pre-releases",
+                release.version
+            ));
+            (release, repository) = fetch_release(Channel::Prerelease)?;
+        }
+        if !has_asset(&release) {
+            if let Some((version_dir, binary_path)) = installed_binary {
+                log(&format!(
+                    "release {} is not fully published yet, keeping {binary_path}",
+                    release.version
+                ));
+                host.make_file_executable(binary_path)?;
+                return Ok(ReleaseChoice::Keep {
+                    binary: self.binary(
+                        binary_path.clone(),
+                        installed_version(version_dir),
+                        BinarySource::Cache,
+                    ),
+                    remember: false,
+                });
+            }
+        }
+        Ok(ReleaseChoice::Download(release, repository))
+    }
+
+    /// Installs `release` from `repository` into its version directory, unless a good copy is
+    /// already there. A rejected download falls back to the previously installed binary.
+    fn install(&self, release: &zed::GithubRelease, repository: &str) -> Result<Install> {
+        let Self {
+            host,
+            settings,
+            server,
+            platform,
+            arch,
+            cache_dir,
+            ..
+        } = *self;
+        let binary_names = &self.binary_names;
+        let installed = &self.installed;
+        let installed_binary = &self.installed_binary;
 
         // Find the asset in the Github release, set the binary path and directory format
-        let asset = release
-            .assets
-            .iter()
-            .find(|asset| asset.name == asset_name)
-            .ok_or_else(|| format!("no asset found matching {:?}", asset_name))?;
+        let missing_asset_error = || {
+            if release.assets.is_empty() {
+                format!(
+                    "release {} has no assets yet; it is probably still being published, \
+                     try again in a few minutes",
+                    release.version
+                )
+            } else {
+                no_asset_error(
+                    server,
+                    repository,
+                    release,
+                    &self.asset_name(),
+                    platform,
+                    arch,
+                )
+            }
+        };
+        let (asset, asset_format) = self.find_asset(release).ok_or_else(missing_asset_error)?;
+        // Forks don't always name assets after what they are, so the format can be forced
+        let asset_format = settings.archive_type.unwrap_or(asset_format);
 
-        let version_dir = format!("ols-{}", release.version);
+        let version_dir_name = format!(
+            "{}-{}",
+            server.name(),
+            version_dir_suffix(&release.version, &asset.download_url)
+        );
+        let version_dir = cache_path(cache_dir, &version_dir_name);
         fs::create_dir_all(&version_dir)
             .map_err(|err| format!("failed to create directory '{version_dir}': {err}"))?;
-        let binary_path = format!(
-            "{version_dir}/ols-{arch}-{os}",
-            arch = match arch {
-                zed::Architecture::Aarch64 => "arm64",
-                zed::Architecture::X86 => "x86",
-                zed::Architecture::X8664 => "x86_64",
-            },
-            os = match platform {
-                zed::Os::Mac => "darwin",
-                zed::Os::Linux => "unknown-linux-gnu",
-                zed::Os::Windows => "pc-windows-msvc",
-            },
-        );
+        let mut binary_path = locate_binary(&version_dir, binary_names)
+            .unwrap_or_else(|| format!("{version_dir}/{}", binary_names[0]));
+
+        // An interrupted or unexpectedly nested extraction can leave a directory where the binary
+        // belongs; remove it so the release is downloaded again.
+        let binary_stat = fs::metadata(&binary_path).ok();
+        if binary_stat.as_ref().is_some_and(|stat| stat.is_dir()) {
+            log(&format!(
+                "warning: {binary_path} is a directory, removing it and downloading again"
+            ));
+            remove_real_dir(&binary_path)
+                .map_err(|err| format!("failed to remove directory '{binary_path}': {err}"))?;
+        }
+
+        // A copied-in cache can hold this version built for another platform
+        let mut is_downloaded = binary_stat.is_some_and(|stat| stat.is_file());
+        if is_downloaded && !is_executable_for(&binary_path, platform, arch) {
+            log(&format!(
+                "warning: {binary_path} is not an executable for this platform, downloading again"
+            ));
+            fs::remove_file(&binary_path)
+                .map_err(|err| format!("failed to remove '{binary_path}': {err}"))?;
+            is_downloaded = false;
+        }
 
         // If the language server binary is not found (not already downloaded), then download it, make it executable, and remove temp files.
-        if !fs::metadata(&binary_path).map_or(false, |stat| stat.is_file()) {
-            zed::set_language_server_installation_status(
-                &language_server_id,
-                &zed::LanguageServerInstallationStatus::Downloading,
-            );
+        if !is_downloaded {
+            // The statuses alone don't explain to newcomers what is being fetched, or why
+            if installed.is_empty() {
+                log(&format!(
+                    "the Odin extension is downloading the Odin language server ({} {}) from \
+                     https://github.com/{repository}; this needs network access once, and the \
+                     download is kept for later sessions",
+                    server.name(),
+                    release.version,
+                ));
+            }
+            host.set_installation_status(&zed::LanguageServerInstallationStatus::Downloading);
 
-            zed::download_file(
-                &asset.download_url,
-                &version_dir,
-                match platform {
-                    zed::Os::Mac | zed::Os::Linux => zed::DownloadedFileType::Zip,
-                    zed::Os::Windows => zed::DownloadedFileType::Zip,
-                },
-            )
-            .map_err(|e| format!("failed to download file: {e}"))?;
+            let download_path = if asset_format.extracts_to_directory() {
+                &version_dir
+            } else {
+                &binary_path
+            };
+            self.trace(format!(
+                "downloading {} as {asset_format:?} to {download_path}",
+                asset.download_url
+            ));
+            let download = || -> Result<String> {
+                // Zed extracts as part of download_file and its status has no extraction phase,
+                // so the log says when each phase finishes instead
+                if asset_format != ArchiveFormat::Uncompressed {
+                    log(&format!(
+                        "downloading and extracting {}, the status shows Downloading until both \
+                         are done",
+                        asset.download_url
+                    ));
+                }
+                let started = now_secs();
+                retry_github_request(installed_binary.is_none(), || {
+                    host.download_file(
+                        &asset.download_url,
+                        download_path,
+                        asset_format.downloaded_file_type(),
+                    )
+                })
+                .map_err(|e| {
+                    if is_rate_limited(&e) {
+                        return rate_limit_error(&e);
+                    }
+                    // Zed applies its own network timeouts; the extension can't set them
+                    if ["timed out", "timeout"]
+                        .iter()
+                        .any(|t| e.to_lowercase().contains(t))
+                    {
+                        return format!(
+                            "download of {} timed out ({e}); check the network or proxy, or \
+                             install {name} yourself and set lsp.ols.binary.path",
+                            asset.download_url,
+                            name = server.name(),
+                        );
+                    }
+                    if settings.check_connectivity && !github_reachable(host) {
+                        return format!(
+                            "no network connectivity to GitHub detected: failed to download {}: \
+                             {e}",
+                            asset.download_url
+                        );
+                    }
+                    format!(
+                        "failed to download {}: {e}. Release assets are served through a \
+                         redirect to GitHub's object storage, which some proxies block; if the \
+                         download keeps failing, install {name} yourself and set \
+                         lsp.ols.binary.path",
+                        asset.download_url,
+                        name = server.name(),
+                    )
+                })?;
+                if let (Some(started), Some(finished)) = (started, now_secs()) {
+                    log(&format!(
+                        "downloaded {} in {}s, checking it",
+                        asset.download_url,
+                        finished.saturating_sub(started)
+                    ));
+                }
+
+                // download_file can't be limited up front, so check what it left behind
+                let downloaded_size = disk_usage(Path::new(download_path));
+                if downloaded_size > settings.max_download_size_mb * BYTES_PER_MB {
+                    return Err(format!(
+                        "{} is {} MB, larger than max_download_size_mb ({})",
+                        asset.download_url,
+                        downloaded_size / BYTES_PER_MB,
+                        settings.max_download_size_mb,
+                    ));
+                }
+
+                // The extracted executable isn't necessarily named the way we expect
+                let binary_path = locate_binary(&version_dir, binary_names).ok_or_else(|| {
+                    format!(
+                        "no executable named any of {binary_names:?} found in {version_dir} after \
+                         downloading {}",
+                        asset.download_url
+                    )
+                })?;
+                self.trace(match fs::metadata(&binary_path) {
+                    Ok(stat) => format!("extracted {binary_path} ({} bytes)", stat.len()),
+                    Err(err) => format!("{binary_path} missing after download: {err}"),
+                });
+
+                // A proxy or captive portal can answer with an HTML page that still gets saved
+                if !is_executable_for(&binary_path, platform, arch) {
+                    return Err(format!(
+                        "{} did not contain an executable for this platform (a proxy or mirror \
+                         may have returned an error page)",
+                        asset.download_url
+                    ));
+                }
+                Ok(binary_path)
+            };
+            // A stale CDN copy or mislabelled asset can be a different ols than the release says.
+            // ols can't be run from here to ask it, but it embeds its version string.
+            let verified_download = || -> Result<String> {
+                let binary_path = download()?;
+                if !settings.verify_version || embeds_version(&binary_path, &release.version) {
+                    return Ok(binary_path);
+                }
+                log(&format!(
+                    "warning: {binary_path} doesn't look like {} {}, downloading it again",
+                    server.name(),
+                    release.version,
+                ));
+                let binary_path = download()?;
+                if !embeds_version(&binary_path, &release.version) {
+                    return Err(format!(
+                        "{} downloaded twice from {} but is not version {} (verify_version)",
+                        server.name(),
+                        asset.download_url,
+                        release.version,
+                    ));
+                }
+                Ok(binary_path)
+            };
+            binary_path = match verified_download() {
+                Ok(binary_path) => binary_path,
+                // Keep working with the previous download rather than leaving no server at all
+                Err(err) => {
+                    let previous = installed_binary
+                        .as_ref()
+                        .filter(|(_, binary_path)| is_executable_for(binary_path, platform, arch));
+                    // A failed install is removed right away rather than left for a later run's
+                    // cleanup, which may no longer consider it the current version
+                    let reinstalling =
+                        matches!(previous, Some((previous_dir, _)) if *previous_dir == version_dir);
+                    let err = if reinstalling {
+                        err
+                    } else {
+                        remove_real_dir(&version_dir).ok();
+                        format!("{err}; the download has been deleted")
+                    };
+                    let Some((previous_dir, previous_path)) = previous else {
+                        return Err(err);
+                    };
+                    log(&format!(
+                        "warning: rejected {} {}: {err}; using the previously downloaded \
+                         {previous_path}",
+                        server.name(),
+                        release.version,
+                    ));
+                    host.make_file_executable(previous_path)?;
+                    return Ok(Install::Previous(self.binary(
+                        previous_path.clone(),
+                        installed_version(previous_dir),
+                        BinarySource::Cache,
+                    )));
+                }
+            };
 
-            zed::make_file_executable(&binary_path)?;
+            host.make_file_executable(&binary_path).inspect_err(|_| {
+                remove_real_dir(&version_dir).ok();
+            })?;
+        } else {
+            host.make_file_executable(&binary_path)?;
+        }
+        Ok(Install::Release {
+            version_dir_name,
+            version_dir,
+            binary_path,
+            downloaded: !is_downloaded,
+        })
+    }
 
-            let entries =
-                fs::read_dir(".").map_err(|e| format!("failed to list working directory {e}"))?;
+    /// Removes the versions superseded by the one just downloaded into `version_dir`.
+    fn clean_up(&self, version_dir_name: &str, version_dir: &str) -> Result<()> {
+        let Self {
+            settings,
+            server,
+            cache_dir,
+            ..
+        } = *self;
+        // With manage_cache off the directory is left exactly as the user arranged it, and
+        // there's nothing to walk the cache for when it held no other version
+        let has_old_versions = self
+            .installed
+            .iter()
+            .any(|(installed_dir, _)| *installed_dir != version_dir);
+        if settings.manage_cache && (has_old_versions || settings.aggressive_cleanup) {
+            // Only old versions are removed unless aggressive_cleanup says everything else in
+            // the working directory may go too; a user-provided cache directory may hold
+            // other files, so there it's old versions only regardless
+            let entries = fs::read_dir(cache_dir.unwrap_or("."))
+                .map_err(|e| format!("failed to list cache directory {e}"))?;
             for entry in entries {
                 let entry = entry.map_err(|e| format!("failed to load directory entry {e}"))?;
-                if entry.file_name().to_str() != Some(&version_dir) {
-                    fs::remove_dir_all(&entry.path()).ok();
+                let file_name = entry.file_name();
+                let Some(file_name) = file_name.to_str() else {
+                    continue;
+                };
+                // Files next to the version directories are our own markers, never old versions
+                let is_dir = entry.file_type().is_ok_and(|ty| ty.is_dir());
+                let removable = is_dir
+                    && ((settings.aggressive_cleanup && cache_dir.is_none())
+                        || is_version_dir(server, file_name));
+                // A superseded version is kept a while, to go back to if the new one misbehaves
+                let version_path = cache_path(cache_dir, file_name);
+                let in_grace_period = settings.cleanup_grace_days > 0
+                    && used_within(&version_path, settings.cleanup_grace_days);
+                if removable && file_name != version_dir_name && !in_grace_period {
+                    self.trace(format!("removing {:?}", entry.path()));
+                    remove_real_dir(entry.path()).ok();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn log(message: &str) {
+    eprintln!("[zed-odin] {message}");
+}
+
+/// Logs a step of resolving the binary when debug_install is on.
+fn trace(settings: &OdinSettings, message: &str) {
+    if settings.debug_install {
+        log(&format!("install: {message}"));
+    }
+}
+
+/// The environment ols is started with, based on the current platform, or `None` for Zed's
+/// own.
+fn ols_environment(host: &impl Host, settings: &OdinSettings) -> Option<Vec<(String, String)>> {
+    let (platform, _) = host.current_platform();
+    let mut environment = match platform {
+        // Only what ols can't start without, so launches don't depend on the user's shell
+        _ if settings.clean_env => Some(
+            host.shell_env()
+                .into_iter()
+                .filter(|(key, _)| CLEAN_ENV_VARS.contains(&key.as_str()))
+                .collect(),
+        ),
+        zed::Os::Mac | zed::Os::Linux if settings.inherit_shell_env => Some(
+            host.shell_env()
+                .into_iter()
+                .filter(|(key, _)| settings.forward_full_env || is_forwarded_var(key, settings))
+                .collect(),
+        ),
+        zed::Os::Mac | zed::Os::Linux | zed::Os::Windows => None,
+    };
+
+    // Explicitly configured variables override the shell environment
+    if !settings.env.is_empty() {
+        let environment = environment.get_or_insert_with(Vec::new);
+        environment.retain(|(key, _)| !settings.env.contains_key(key));
+        environment.extend(
+            settings
+                .env
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+    }
+
+    // ols needs the Odin sources to navigate into `core:`, which it finds through ODIN_ROOT
+    let has_odin_root = environment
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .any(|(key, _)| key == "ODIN_ROOT");
+    if !has_odin_root {
+        match host.which("odin") {
+            Some(odin) if settings.set_odin_root => {
+                if let Some(odin_root) = Path::new(&odin).parent() {
+                    environment.get_or_insert_with(Vec::new).push((
+                        "ODIN_ROOT".to_string(),
+                        odin_root.to_string_lossy().into_owned(),
+                    ));
                 }
             }
+            Some(_) => {}
+            None => log(
+                "warning: neither ODIN_ROOT nor odin on PATH was found, so navigation into \
+                 core: will not resolve; install Odin from https://odin-lang.org/docs/install/",
+            ),
+        }
+    }
+    environment
+}
+
+/// Warns about settings that cannot take effect with the extension API version in use.
+fn warn_unsupported_settings(cache_dir: Option<&str>) {
+    if let Some(cache_dir) = cache_dir {
+        if Path::new(cache_dir).is_absolute() {
+            log(&format!(
+                "warning: cache directory {cache_dir:?} is absolute, but zed_extension_api \
+                 {ZED_EXTENSION_API_VERSION} can only download into the extension's working \
+                 directory; use a relative path instead"
+            ));
         }
+    }
+}
 
-        // Set the cached binary path and return it.
-        self.cached_binary_path = Some(binary_path.clone());
-        Ok(OlsBinary {
-            path: binary_path,
-            args,
-            environment,
+/// Compares release tags by semver precedence, ignoring a leading `v` and build metadata, or
+/// `None` if either isn't a semver version.
+fn semver_cmp(a: &str, b: &str) -> Option<Ordering> {
+    fn parse(tag: &str) -> Option<([u64; 3], Vec<&str>)> {
+        let version = tag.strip_prefix('v').unwrap_or(tag);
+        let version = version
+            .split_once('+')
+            .map_or(version, |(version, _)| version);
+        let (core, pre_release) = match version.split_once('-') {
+            Some((core, pre_release)) => (core, pre_release.split('.').collect()),
+            None => (version, Vec::new()),
+        };
+        let mut parts = core.split('.').map(|part| part.parse().ok());
+        let core = [parts.next()??, parts.next()??, parts.next()??];
+        parts.next().is_none().then_some((core, pre_release))
+    }
+    let ((a_core, a_pre), (b_core, b_pre)) = (parse(a)?, parse(b)?);
+    // A pre-release sorts before its release; identifiers compare numerically when both are
+    // numbers, numbers sort before words, and a longer list wins a tie
+    let pre_release = match (a_pre.is_empty(), b_pre.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a_pre
+            .iter()
+            .zip(&b_pre)
+            .map(|(a, b)| match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => a.cmp(b),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a_pre.len().cmp(&b_pre.len())),
+    };
+    Some(a_core.cmp(&b_core).then(pre_release))
+}
+
+/// Returns the `dev-YYYY-MM` release name contained in `text`, if any.
+fn dev_release(text: &str) -> Option<&str> {
+    text.match_indices("dev-").find_map(|(ix, _)| {
+        let candidate = text.get(ix..ix + "dev-YYYY-MM".len())?;
+        let bytes = candidate.as_bytes();
+        let is_release = bytes[4..8].iter().all(u8::is_ascii_digit)
+            && bytes[8] == b'-'
+            && bytes[9..11].iter().all(u8::is_ascii_digit);
+        is_release.then_some(candidate)
+    })
+}
+
+/// Whether a shell environment variable is one ols, the Odin toolchain or the extension itself
+/// may need: search paths, home and temp directories, locale, proxies and certificates.
+fn is_forwarded_var(key: &str, settings: &OdinSettings) -> bool {
+    const VARS: &[&str] = &[
+        "PATH", "HOME", "USER", "SHELL", "TMPDIR", "TMP", "TEMP", "LANG", "LANGUAGE", "TZ",
+    ];
+    const PREFIXES: &[&str] = &["ODIN_", "OLS_", "LC_", "XDG_", "SSL_CERT_"];
+    VARS.contains(&key)
+        || PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+        || key.to_ascii_uppercase().ends_with("_PROXY")
+        || *key == settings.binary_env_var
+}
+
+/// Whether `version` is a monthly release within [`TESTED_OLS_RELEASES`]. `dev-YYYY-MM` names
+/// sort chronologically, so they can be compared as strings.
+fn is_tested_release(version: &str) -> bool {
+    let (oldest, newest) = TESTED_OLS_RELEASES;
+    dev_release(version).is_some_and(|release| (oldest..=newest).contains(&release))
+}
+
+/// Best-effort detection of the installed Odin release, from the name of the directory it was
+/// unpacked into (e.g. `odin-linux-amd64-dev-2024-04`), found via `ODIN_ROOT` or PATH.
+fn detect_odin_version(
+    host: &impl Host,
+    environment: Option<&[(String, String)]>,
+) -> Option<String> {
+    let odin_root = environment
+        .unwrap_or_default()
+        .iter()
+        .find(|(key, _)| key == "ODIN_ROOT")
+        .map(|(_, value)| value.clone());
+    odin_root
+        .into_iter()
+        .chain(host.which("odin"))
+        .find_map(|path| dev_release(&path).map(str::to_string))
+}
+
+/// Finds the release asset named after the first of `stems` that has one, and its archive
+/// format. A `configured` format is the only one accepted; otherwise every supported format is
+/// tried, starting with `preferred`.
+fn find_asset<'a>(
+    release: &'a zed::GithubRelease,
+    stems: &[String],
+    configured: Option<ArchiveFormat>,
+    preferred: ArchiveFormat,
+) -> Option<(&'a zed::GithubReleaseAsset, ArchiveFormat)> {
+    let formats = match configured {
+        Some(format) => vec![format],
+        None => std::iter::once(preferred)
+            .chain(ArchiveFormat::ALL.into_iter().filter(|&f| f != preferred))
+            .collect(),
+    };
+    stems.iter().find_map(|stem| {
+        formats.iter().find_map(|&format| {
+            let name = format.asset_name(stem);
+            release
+                .assets
+                .iter()
+                .find(|asset| asset.name == name)
+                .map(|asset| (asset, format))
         })
+    })
+}
+
+/// The repositories to look ols releases up in, in order: `repositories`, or else upstream's.
+fn release_repositories(settings: &OdinSettings) -> Vec<String> {
+    match &settings.repositories {
+        Some(repositories) if !repositories.is_empty() => repositories.clone(),
+        _ => vec![settings.server.implementation().repository().to_string()],
     }
 }
 
-impl zed::Extension for OdinExtension {
-    fn new() -> Self {
-        Self {
-            cached_binary_path: None,
+/// Explains how to install the server by hand when no release asset matches this platform.
+fn no_asset_error(
+    server: &dyn LanguageServer,
+    repository: &str,
+    release: &zed::GithubRelease,
+    asset_name: &str,
+    platform: zed::Os,
+    arch: zed::Architecture,
+) -> String {
+    format!(
+        "no asset found matching {asset_name:?} in {repository} release {version} \
+         (detected platform: {platform:?}, architecture: {arch:?}). \
+         Download or build {name} manually from https://github.com/{repository}, then point \
+         the extension at it in your Zed settings:\n\n\
+         \"lsp\": {{ \"ols\": {{ \"binary\": {{ \"path\": \"/path/to/{name}\" }} }} }}",
+        version = release.version,
+        name = server.name(),
+    )
+}
+
+/// Whether `path` matches `pattern`, either as a path prefix or as a glob where `*` matches
+/// within a path component, `**` matches across components and `?` matches a single character.
+fn matches_root_pattern(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+    if !pattern.contains(['*', '?']) {
+        return path
+            .strip_prefix(pattern)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    }
+
+    fn glob(pattern: &[u8], path: &[u8]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            [b'*', b'*', rest @ ..] => (0..=path.len()).any(|ix| glob(rest, &path[ix..])),
+            [b'*', rest @ ..] => {
+                let component_len = path.iter().position(|&c| c == b'/').unwrap_or(path.len());
+                (0..=component_len).any(|ix| glob(rest, &path[ix..]))
+            }
+            [b'?', rest @ ..] => path.first().is_some_and(|&c| c != b'/') && glob(rest, &path[1..]),
+            [c, rest @ ..] => path.first() == Some(c) && glob(rest, &path[1..]),
         }
     }
+    glob(pattern.as_bytes(), path.as_bytes())
+}
 
-    fn language_server_command(
-        &mut self,
-        language_server_id: &LanguageServerId,
-        worktree: &zed::Worktree,
-    ) -> Result<zed::Command> {
-        let ols_binary = self.language_server_binary(language_server_id, worktree)?;
-        Ok(zed::Command {
-            command: ols_binary.path,
-            args: ols_binary.args.unwrap_or_default(),
-            env: ols_binary.environment.unwrap_or_default(),
+/// Returns a path-safe name for a release's version directory.
+///
+/// Characters outside `[A-Za-z0-9._-]` are replaced, and if nothing usable remains (e.g. an empty
+/// version from a fork's release metadata), a hash of the asset URL is used instead.
+fn version_dir_suffix(version: &str, download_url: &str) -> String {
+    let sanitized: String = version
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
         })
+        .collect();
+
+    if sanitized.chars().any(|c| c.is_ascii_alphanumeric()) {
+        sanitized
+    } else {
+        format!("{:016x}", stable_hash(download_url))
     }
 }
 
-zed::register_extension!(OdinExtension);
+/// FNV-1a, so names derived from it stay stable across runs and toolchains.
+fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns the path of `name` inside the cache directory, or the working directory if unset.
+fn cache_path(cache_dir: Option<&str>, name: &str) -> String {
+    match cache_dir {
+        Some(cache_dir) => Path::new(cache_dir)
+            .join(name)
+            .to_string_lossy()
+            .into_owned(),
+        None => name.to_string(),
+    }
+}
+
+/// Whether `file_name` is one of `server`'s version directories.
+fn is_version_dir(server: &dyn LanguageServer, file_name: &str) -> bool {
+    file_name
+        .strip_prefix(server.name())
+        .is_some_and(|rest| rest.starts_with('-'))
+}
+
+/// Returns the version directory and binary path of every previously downloaded server.
+///
+/// A cache directory can be copied between machines or baked into a container image, so
+/// binaries built for another platform are skipped rather than trusted. The most recently
+/// installed or update-checked version comes first, so a superseded version that is still kept
+/// around doesn't win.
+fn installed_binaries(
+    cache_dir: Option<&str>,
+    server: &dyn LanguageServer,
+    binary_names: &[String],
+    platform: zed::Os,
+    arch: zed::Architecture,
+) -> Vec<(String, String)> {
+    let Ok(entries) = fs::read_dir(cache_dir.unwrap_or(".")) else {
+        return Vec::new();
+    };
+    let mut installed = entries
+        .flatten()
+        .filter_map(|entry| {
+            let version_dir_name = entry.file_name().into_string().ok()?;
+            if !is_version_dir(server, &version_dir_name) {
+                return None;
+            }
+            let version_dir = cache_path(cache_dir, &version_dir_name);
+            let binary_path = locate_binary(&version_dir, binary_names)?;
+            if !is_executable_for(&binary_path, platform, arch) {
+                log(&format!(
+                    "warning: ignoring {binary_path}, which is not an executable for this platform \
+                     and architecture"
+                ));
+                return None;
+            }
+            Some((version_dir, binary_path))
+        })
+        .collect::<Vec<_>>();
+    installed.sort_by_cached_key(|(version_dir, _)| {
+        let last_check = fs::read_to_string(format!("{version_dir}/{LAST_UPDATE_CHECK_FILE}"));
+        std::cmp::Reverse(
+            last_check
+                .ok()
+                .and_then(|last_check| last_check.trim().parse::<u64>().ok()),
+        )
+    });
+    installed
+}
+
+/// Returns the path of the first of `binary_names` that is a file in `version_dir`, or failing
+/// that in one of its subdirectories, for archives that wrap their contents in a folder. Other
+/// extracted files (licenses, ols's `builtin` sources) are left where they are.
+fn locate_binary(version_dir: &str, binary_names: &[String]) -> Option<String> {
+    let find_in = |dir: &str| {
+        binary_names
+            .iter()
+            .map(|binary_name| format!("{dir}/{binary_name}"))
+            .find(|binary_path| fs::metadata(binary_path).is_ok_and(|stat| stat.is_file()))
+    };
+    find_in(version_dir).or_else(|| {
+        let mut subdirs = fs::read_dir(version_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|ty| ty.is_dir()))
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect::<Vec<_>>();
+        subdirs.sort();
+        subdirs
+            .iter()
+            .find_map(|subdir| find_in(&format!("{version_dir}/{subdir}")))
+    })
+}
+
+/// Returns the version pinned by the `version` setting or the project's `.ols-version` file.
+fn pinned_version(host: &impl Host, settings: &OdinSettings) -> Option<String> {
+    let file_version = host
+        .read_text_file(OLS_VERSION_FILE)
+        .ok()
+        .map(|contents| contents.trim().to_string())
+        .filter(|version| !version.is_empty());
+    match (&settings.version, file_version) {
+        (Some(version), Some(file_version)) => {
+            if *version != file_version {
+                log(&format!(
+                    "the version setting ({version}) overrides {OLS_VERSION_FILE} ({file_version})"
+                ));
+            }
+            Some(version.clone())
+        }
+        (Some(version), None) => Some(version.clone()),
+        (None, file_version) => file_version,
+    }
+}
+
+/// Returns the Odin release pinned by the `odin_version` setting or the project's
+/// `.odin-version` file, the setting winning.
+fn pinned_odin_version(host: &impl Host, settings: &OdinSettings) -> Option<String> {
+    settings.odin_version.clone().or_else(|| {
+        host.read_text_file(ODIN_VERSION_FILE)
+            .ok()
+            .map(|contents| contents.trim().to_string())
+            .filter(|version| !version.is_empty())
+    })
+}
+
+fn installed_version(version_dir: &str) -> Option<String> {
+    let path = format!("{version_dir}/{INSTALLED_VERSION_FILE}");
+    let version = fs::read_to_string(&path).ok()?;
+    if version.trim().is_empty() || version.contains(char::is_control) {
+        discard_state(&path);
+        return None;
+    }
+    Some(version)
+}
+
+fn record_installed_version(version_dir: &str, version: &str) {
+    write_state(&format!("{version_dir}/{INSTALLED_VERSION_FILE}"), version).ok();
+}
+
+/// Writes a state file through a temporary file and a rename, so an interrupted write can't
+/// leave it half-written.
+fn write_state(path: &str, contents: &str) -> io::Result<()> {
+    let temp_path = format!("{path}.tmp");
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)
+}
+
+/// Removes a state file that couldn't be parsed, so the state is rebuilt from scratch.
+fn discard_state(path: &str) {
+    log(&format!("warning: discarding corrupted state file {path}"));
+    fs::remove_file(path).ok();
+}
+
+/// Whether the file at `path` is an executable for `platform` and `arch`: ELF on Linux, Mach-O
+/// on macOS and PE on Windows, built for the architecture its header names.
+fn is_executable_for(path: &str, platform: zed::Os, arch: zed::Architecture) -> bool {
+    let mut header = Vec::new();
+    let Ok(_) = fs::File::open(path).and_then(|file| file.take(4096).read_to_end(&mut header))
+    else {
+        return false;
+    };
+    is_executable_header(&header, platform, arch)
+}
+
+fn is_executable_header(header: &[u8], platform: zed::Os, arch: zed::Architecture) -> bool {
+    let u16_at = |offset: usize, big_endian: bool| {
+        let bytes: [u8; 2] = header.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |offset: usize, big_endian: bool| {
+        let bytes: [u8; 4] = header.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+    match platform {
+        zed::Os::Linux => {
+            // e_machine, in the byte order e_ident's EI_DATA gives
+            let machine = match arch {
+                zed::Architecture::Aarch64 => 0xb7,
+                zed::Architecture::X86 => 0x03,
+                zed::Architecture::X8664 => 0x3e,
+            };
+            header.starts_with(&[0x7f, b'E', b'L', b'F'])
+                && u16_at(18, header.get(5) == Some(&2)) == Some(machine)
+        }
+        zed::Os::Mac => {
+            let cpu_type = match arch {
+                zed::Architecture::Aarch64 => 0x0100_000c,
+                zed::Architecture::X86 => 0x07,
+                zed::Architecture::X8664 => 0x0100_0007,
+            };
+            match header.get(..4) {
+                Some([0xcf | 0xce, 0xfa, 0xed, 0xfe]) => u32_at(4, false) == Some(cpu_type),
+                Some([0xfe, 0xed, 0xfa, 0xcf | 0xce]) => u32_at(4, true) == Some(cpu_type),
+                // A universal binary is fine if any of its slices fits
+                Some([0xca, 0xfe, 0xba, 0xbe]) => (0..u32_at(4, true).unwrap_or(0) as usize)
+                    .take(header.len() / 20)
+                    .any(|ix| u32_at(8 + ix * 20, true) == Some(cpu_type)),
+                _ => false,
+            }
+        }
+        zed::Os::Windows => {
+            let machine = match arch {
+                zed::Architecture::Aarch64 => 0xaa64,
+                zed::Architecture::X86 => 0x014c,
+                zed::Architecture::X8664 => 0x8664,
+            };
+            let Some(pe_offset) = u32_at(0x3c, false).map(|offset| offset as usize) else {
+                return false;
+            };
+            header.starts_with(b"MZ")
+                && header.get(pe_offset..pe_offset + 4) == Some(b"PE\0\0")
+                && u16_at(pe_offset + 4, false) == Some(machine)
+        }
+    }
+}
+
+/// Whether the executable at `path` contains `version`, as ols binaries contain their own version.
+fn embeds_version(path: &str, version: &str) -> bool {
+    let Ok(contents) = fs::read(path) else {
+        return false;
+    };
+    !version.is_empty()
+        && contents
+            .windows(version.len())
+            .any(|window| window == version.as_bytes())
+}
+
+/// Total size in bytes of the file or directory tree at `path`.
+fn disk_usage(path: &Path) -> u64 {
+    // A link in the archive may point anywhere, including back up the tree, so links are skipped
+    match fs::symlink_metadata(path) {
+        Ok(stat) if stat.is_symlink() => 0,
+        Ok(stat) if stat.is_dir() => fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| disk_usage(&entry.path()))
+            .sum(),
+        Ok(stat) => stat.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Checks that files can be created in the cache directory.
+fn ensure_cache_writable(cache_dir: Option<&str>) -> Result<()> {
+    let dir = cache_dir.unwrap_or(".");
+    let probe = cache_path(cache_dir, WRITE_PROBE_FILE);
+    fs::create_dir_all(dir)
+        .and_then(|()| fs::write(&probe, ""))
+        .and_then(|()| fs::remove_file(&probe))
+        .map_err(|err| format!("cannot write to {dir:?} ({err})"))
+}
+
+/// Removes every downloaded version directory of `server`, leaving any other files in place.
+fn clear_download_cache(cache_dir: Option<&str>, server: &dyn LanguageServer) -> Result<()> {
+    let entries = match fs::read_dir(cache_dir.unwrap_or(".")) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("failed to list cache directory {e}")),
+    };
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("failed to load directory entry {e}"))?;
+        let is_dir = entry.file_type().is_ok_and(|ty| ty.is_dir());
+        if is_dir
+            && entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| is_version_dir(server, name))
+        {
+            remove_real_dir(entry.path())
+                .map_err(|e| format!("failed to remove {:?}: {e}", entry.path()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the version directories of `server` that don't record their release, which older
+/// releases of the extension left behind with a different layout.
+fn remove_unrecorded_versions(cache_dir: Option<&str>, server: &dyn LanguageServer) {
+    let Ok(entries) = fs::read_dir(cache_dir.unwrap_or(".")) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let version_dir = cache_path(cache_dir, &file_name);
+        if entry.file_type().is_ok_and(|ty| ty.is_dir())
+            && is_version_dir(server, &file_name)
+            && installed_version(&version_dir).is_none()
+        {
+            log(&format!(
+                "removing {version_dir}, downloaded by an older release of the extension"
+            ));
+            remove_real_dir(&version_dir).ok();
+        }
+    }
+}
+
+/// `fs::remove_dir_all`, except that a symlink is refused rather than removed, so cleanup only
+/// ever deletes real directories inside the cache. Links inside the directory are unlinked
+/// without following them.
+fn remove_real_dir(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Err(io::Error::other(format!("{path:?} is a symlink")));
+    }
+    fs::remove_dir_all(path)
+}
+
+/// Whether GitHub answers at all, to tell users without a connection from failed downloads.
+fn github_reachable(host: &impl Host) -> bool {
+    let reachable = host
+        .download_file(
+            REACHABILITY_PROBE_URL,
+            REACHABILITY_PROBE_FILE,
+            zed::DownloadedFileType::Uncompressed,
+        )
+        .is_ok();
+    fs::remove_file(REACHABILITY_PROBE_FILE).ok();
+    reachable
+}
+
+/// Runs a GitHub request, retrying it once if GitHub sent back something that wasn't the JSON
+/// asked for. A rate limited request is only retried, after [`RATE_LIMIT_RETRY_DELAY`], when
+/// `nothing_installed`; otherwise callers fall back to the installed binary rather than hold up
+/// startup.
+fn retry_github_request<T>(nothing_installed: bool, request: impl Fn() -> Result<T>) -> Result<T> {
+    match request() {
+        Err(err) if is_malformed_response(&err) => {
+            log(&format!(
+                "unexpected response from GitHub ({err}), retrying"
+            ));
+            request()
+        }
+        Err(err) if nothing_installed && is_rate_limited(&err) => {
+            log(&format!(
+                "GitHub rate limited the request ({err}), retrying in {} seconds",
+                RATE_LIMIT_RETRY_DELAY.as_secs()
+            ));
+            thread::sleep(RATE_LIMIT_RETRY_DELAY);
+            request()
+        }
+        result => result,
+    }
+}
+
+/// Whether an error is a GitHub response that couldn't be parsed, typically an HTML page or a
+/// truncated body from a proxy rather than a problem with the release itself: one of ours from
+/// [`github::fetch_json`], or the Zed host failing to deserialize a release.
+fn is_malformed_response(err: &str) -> bool {
+    err.contains(github::MALFORMED_RESPONSE) || err.to_lowercase().contains("deserializ")
+}
+
+/// Whether an error from the Zed host is GitHub's primary or secondary rate limit, which it
+/// reports as a 403 or 429 mentioning the limit.
+fn is_rate_limited(err: &str) -> bool {
+    let err = err.to_lowercase();
+    err.contains("rate limit") || err.contains("429")
+}
+
+fn rate_limit_error(err: &str) -> String {
+    format!(
+        "GitHub is rate limiting requests ({err}); try again in a few minutes, or set GITHUB_TOKEN \
+         in the environment Zed is started from for a higher limit"
+    )
+}
+
+fn now_secs() -> Option<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_secs())
+}
+
+/// Whether GitHub should be consulted for a newer release, given an installed `version_dir`.
+fn update_check_due(interval: UpdateCheckInterval, version_dir: &str) -> bool {
+    match interval {
+        UpdateCheckInterval::Never => false,
+        UpdateCheckInterval::Startup => true,
+        UpdateCheckInterval::Daily => {
+            let path = format!("{version_dir}/{LAST_UPDATE_CHECK_FILE}");
+            let last_check = fs::read_to_string(&path).ok().and_then(|contents| {
+                let last_check = contents.trim().parse::<u64>().ok();
+                if last_check.is_none() {
+                    discard_state(&path);
+                }
+                last_check
+            });
+            match (last_check, now_secs()) {
+                (Some(last_check), Some(now)) => now.saturating_sub(last_check) >= SECONDS_PER_DAY,
+                _ => true,
+            }
+        }
+    }
+}
+
+fn record_update_check(version_dir: &str) {
+    if let Some(now) = now_secs() {
+        write_state(
+            &format!("{version_dir}/{LAST_UPDATE_CHECK_FILE}"),
+            &now.to_string(),
+        )
+        .ok();
+    }
+}
+
+/// Records in its version directory that a downloaded binary is being started.
+fn record_last_used(binary_path: &str) {
+    let version_dir = Path::new(binary_path)
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join(INSTALLED_VERSION_FILE).is_file());
+    if let (Some(version_dir), Some(now)) = (version_dir, now_secs()) {
+        write_state(
+            &version_dir.join(LAST_USED_FILE).to_string_lossy(),
+            &now.to_string(),
+        )
+        .ok();
+    }
+}
+
+/// Whether the binary in `version_dir` was started in the last `days` days. Versions that never
+/// recorded it count from when they were downloaded.
+fn used_within(version_dir: &str, days: u64) -> bool {
+    let last_used = fs::read_to_string(format!("{version_dir}/{LAST_USED_FILE}"))
+        .ok()
+        .and_then(|contents| contents.trim().parse::<u64>().ok())
+        .or_else(|| {
+            let modified = fs::metadata(version_dir).ok()?.modified().ok()?;
+            Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+        });
+    match (last_used, now_secs()) {
+        (Some(last_used), Some(now)) => now.saturating_sub(last_used) < days * SECONDS_PER_DAY,
+        _ => false,
+    }
+}
+
+/// Writes the absolute path of the binary about to start to [`ACTIVE_BINARY_FILE`].
+fn record_active_binary(binary_path: &str) {
+    let path = std::env::current_dir()
+        .map(|dir| dir.join(binary_path))
+        .unwrap_or_else(|_| binary_path.into());
+    if let Err(err) = write_state(ACTIVE_BINARY_FILE, &path.to_string_lossy()) {
+        log(&format!(
+            "warning: failed to write {ACTIVE_BINARY_FILE}: {err}"
+        ));
+    }
+}
+
+/// Quotes `text` as a single word for `sh`.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+impl zed::Extension for OdinExtension {
+    fn new() -> Self {
+        Self {
+            cached_binary_paths: HashMap::new(),
+            label_settings: HashMap::new(),
+            ols_releases: HashMap::new(),
+            unversioned_path_binaries: HashSet::new(),
+            reported_untested_releases: HashSet::new(),
+        }
+    }
+
+    fn language_server_command(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<zed::Command> {
+        let lsp_settings =
+            LspSettings::for_worktree(language_server_id.as_ref(), worktree).unwrap_or_default();
+        let settings = OdinSettings::from_lsp_settings(&lsp_settings)?;
+
+        // Echo what was actually parsed, defaults included, since settings can come from several
+        // scopes and it's otherwise hard to tell which value won
+        if settings.debug_install {
+            log(&format!(
+                "effective settings for {language_server_id} in {}: binary {:?}, {:?}",
+                worktree.root_path(),
+                lsp_settings.binary,
+                settings.redacted(),
+            ));
+        }
+
+        // Decline to start outside the roots the user enabled ols for
+        let root_path = worktree.root_path();
+        if let Some(root_patterns) = &settings.root_patterns {
+            if !root_patterns
+                .iter()
+                .any(|pattern| matches_root_pattern(pattern, &root_path))
+            {
+                return Err(format!(
+                    "not starting ols: worktree {root_path} does not match any of the \
+                     root_patterns {root_patterns:?}"
+                ));
+            }
+        }
+
+        self.label_settings.insert(
+            language_server_id.clone(),
+            LabelSettings {
+                detail: settings.completion_detail,
+                highlights: settings.label_highlights.clone(),
+                package_prefix: settings.completion_package_prefix,
+            },
+        );
+
+        let host = ZedHost {
+            language_server_id,
+            worktree,
+        };
+        let ols_binary = self.language_server_binary(
+            &host,
+            language_server_id.as_ref(),
+            lsp_settings,
+            &settings,
+        )?;
+        log(&format!(
+            "starting {} from {} (version {}, zed_extension_api {ZED_EXTENSION_API_VERSION})",
+            ols_binary.path,
+            ols_binary.source.describe(),
+            ols_binary.version.as_deref().unwrap_or("unknown"),
+        ));
+        record_active_binary(&ols_binary.path);
+        let release = ols_binary
+            .version
+            .as_deref()
+            .and_then(dev_release)
+            .or_else(|| dev_release(&ols_binary.path));
+        if let Some(release) = release {
+            self.ols_releases
+                .insert(language_server_id.clone(), release.to_string());
+        } else {
+            self.ols_releases.remove(language_server_id);
+        }
+        if ols_binary.version.is_some() {
+            record_last_used(&ols_binary.path);
+        }
+        if settings.check_only {
+            log(
+                "check_only is on: ols reports diagnostics, but hover, symbols, semantic tokens, \
+                 inlay hints, references and formatting are off",
+            );
+        }
+        // Releases are looked up where this binary's came from, or else where a download would
+        let repository = ols_binary
+            .repository
+            .clone()
+            .unwrap_or_else(|| release_repositories(&settings).remove(0));
+        if settings.health_check {
+            let report = health::report(
+                &host,
+                settings.server.implementation(),
+                &repository,
+                &ols_binary,
+            );
+            log(&format!("health check:\n{report}"));
+            fs::write(HEALTH_REPORT_FILE, &report).ok();
+        }
+        if settings.release_notes {
+            let notes = release_notes::release_notes(
+                &host,
+                settings.server.implementation(),
+                &repository,
+                settings.channel,
+                ols_binary.version.as_deref(),
+            );
+            log(&format!("release notes:\n{notes}"));
+        }
+        let mut command = zed::Command {
+            command: ols_binary.path,
+            args: settings
+                .platform_args(host.current_platform().0)
+                .iter()
+                .cloned()
+                .chain(ols_binary.args.unwrap_or_default())
+                .collect(),
+            env: ols_binary.environment.unwrap_or_default(),
+        };
+
+        // The wrapper runs ols itself, so it ends up inside any pre-start shell below
+        if let Some((wrapper, wrapper_args)) = settings
+            .wrapper
+            .as_deref()
+            .and_then(|wrapper| wrapper.split_first())
+        {
+            command.args.splice(
+                0..0,
+                wrapper_args
+                    .iter()
+                    .cloned()
+                    .chain([std::mem::take(&mut command.command)]),
+            );
+            command.command = wrapper.clone();
+        }
+
+        // The extension API can't run processes itself, so the pre-start command runs in a shell
+        // that only execs ols if it succeeds; its output goes to stderr, which lands in the ols
+        // log, so it can't corrupt the LSP stream on stdout.
+        if let Some(pre_start_command) = &settings.pre_start_command {
+            if host.current_platform().0 == zed::Os::Windows {
+                return Err("pre_start_command is not supported on Windows".to_string());
+            }
+            let timeout = settings.startup_timeout_secs;
+            let script = if timeout == 0 {
+                format!("({pre_start_command}) >&2 && exec \"$0\" \"$@\"")
+            } else {
+                // A watchdog kills the command if it outlives the timeout (exit status 143). The
+                // command runs in its own process group so that whatever it spawned dies with it,
+                // and the watchdog's sleep, orphaned once it's killed, must not hold ols's stdout.
+                let quoted = shell_quote(pre_start_command);
+                format!(
+                    "if command -v setsid >/dev/null 2>&1; then setsid sh -c {quoted} >&2 & \
+                     else set -m; sh -c {quoted} >&2 & fi; pid=$!; set +m; \
+                     (sleep {timeout}; kill -- -$pid 2>/dev/null) >/dev/null 2>&1 & watchdog=$!; \
+                     wait $pid; status=$?; kill $watchdog 2>/dev/null; \
+                     if [ $status -eq 143 ]; then \
+                     echo \"[zed-odin] pre_start_command did not finish within {timeout}s \
+                     (startup_timeout_secs); not starting ols\" >&2; exit 1; fi; \
+                     [ $status -eq 0 ] && exec \"$0\" \"$@\"; exit $status"
+                )
+            };
+            command
+                .args
+                .splice(0..0, ["-c".to_string(), script, command.command]);
+            command.command = "sh".to_string();
+        }
+
+        // Zed doesn't set ols's working directory, so paths relative to it (collections in
+        // ols.json, for one) may not resolve against the project; a shell can change into it
+        if settings.run_in_worktree_root {
+            if host.current_platform().0 == zed::Os::Windows {
+                return Err("run_in_worktree_root is not supported on Windows".to_string());
+            }
+            command
+                .env
+                .push(("ZED_WORKTREE_ROOT".to_string(), worktree.root_path()));
+            command.args.splice(
+                0..0,
+                [
+                    "-c".to_string(),
+                    "cd \"$ZED_WORKTREE_ROOT\" && exec \"$0\" \"$@\"".to_string(),
+                    command.command,
+                ],
+            );
+            command.command = "sh".to_string();
+        }
+
+        Ok(command)
+    }
+
+    fn language_server_initialization_options(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        let lsp_settings =
+            LspSettings::for_worktree(language_server_id.as_ref(), worktree).unwrap_or_default();
+        let settings = OdinSettings::from_lsp_settings(&lsp_settings)?;
+        let host = ZedHost {
+            language_server_id,
+            worktree,
+        };
+        let options = init_options::initialization_options(&host, &lsp_settings, &settings);
+        // Options ols doesn't know are silently ignored, so an old ols would quietly lose them
+        if let (Some(options), Some(release)) =
+            (&options, self.ols_releases.get(language_server_id))
+        {
+            for (option, since) in init_options::unsupported_options(options, release) {
+                log(&format!(
+                    "warning: ols {release} predates {option}, which needs {since} or newer, so \
+                     it won't take effect"
+                ));
+            }
+        }
+        Ok(options)
+    }
+
+    /// ols re-reads its analyzer options from `workspace/didChangeConfiguration`, so settings
+    /// changes that don't affect which binary runs are applied without restarting it.
+    fn language_server_workspace_configuration(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        let lsp_settings =
+            LspSettings::for_worktree(language_server_id.as_ref(), worktree).unwrap_or_default();
+        let settings = OdinSettings::from_lsp_settings(&lsp_settings)?;
+        let host = ZedHost {
+            language_server_id,
+            worktree,
+        };
+        Ok(init_options::initialization_options(
+            &host,
+            &lsp_settings,
+            &settings,
+        ))
+    }
+
+    fn label_for_completion(
+        &self,
+        language_server_id: &LanguageServerId,
+        completion: zed::lsp::Completion,
+    ) -> Option<zed::CodeLabel> {
+        let settings = self.label_settings.get(language_server_id);
+        labels::completion_label(&completion, settings.unwrap_or(&LabelSettings::default()))
+    }
+
+    fn label_for_symbol(
+        &self,
+        language_server_id: &LanguageServerId,
+        symbol: zed::lsp::Symbol,
+    ) -> Option<zed::CodeLabel> {
+        let settings = self.label_settings.get(language_server_id);
+        labels::symbol_label(&symbol, settings.unwrap_or(&LabelSettings::default()))
+    }
+}
+
+zed::register_extension!(OdinExtension);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use host::FakeHost;
+    use settings::VersionManager;
+    use zed::settings::BinarySettings;
+
+    const REPOSITORY: &str = "DanielGavin/ols";
+    const BINARY_NAME: &str = "ols-x86_64-unknown-linux-gnu";
+    const ASSET_NAME: &str = "ols-x86_64-unknown-linux-gnu.zip";
+
+    /// An empty cache directory of its own for each test, removed when it's dropped.
+    struct CacheDir(String);
+
+    impl CacheDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("zed-odin-{test}-{}", std::process::id()));
+            fs::remove_dir_all(&dir).ok();
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir.to_string_lossy().into_owned())
+        }
+    }
+
+    impl std::ops::Deref for CacheDir {
+        type Target = str;
+
+        fn deref(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl std::fmt::Display for CacheDir {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    impl Drop for CacheDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn settings(cache_dir: &str) -> OdinSettings {
+        OdinSettings {
+            cache_dir: Some(cache_dir.to_string()),
+            check_connectivity: false,
+            ..OdinSettings::default()
+        }
+    }
+
+    fn ols_binary(version: &str) -> Vec<u8> {
+        let mut binary = elf_header(0x3e);
+        binary.extend(version.as_bytes());
+        binary
+    }
+
+    fn asset_url(version: &str, asset_name: &str) -> String {
+        format!("https://github.com/{REPOSITORY}/releases/download/{version}/{asset_name}")
+    }
+
+    fn release(version: &str, asset_names: &[&str]) -> (zed::GithubRelease, bool) {
+        let assets = asset_names
+            .iter()
+            .map(|name| zed::GithubReleaseAsset {
+                name: name.to_string(),
+                download_url: asset_url(version, name),
+            })
+            .collect();
+        let release = zed::GithubRelease {
+            version: version.to_string(),
+            assets,
+        };
+        (release, false)
+    }
+
+    /// Lays out a download of `version` the way the extension leaves one, returning its binary.
+    fn install(cache_dir: &str, version: &str, last_update_check: u64) -> String {
+        let version_dir = format!("{cache_dir}/ols-{version}");
+        fs::create_dir_all(&version_dir).unwrap();
+        let binary_path = format!("{version_dir}/{BINARY_NAME}");
+        fs::write(&binary_path, ols_binary(version)).unwrap();
+        record_installed_version(&version_dir, version);
+        write_state(
+            &format!("{version_dir}/{LAST_UPDATE_CHECK_FILE}"),
+            &last_update_check.to_string(),
+        )
+        .unwrap();
+        binary_path
+    }
+
+    fn resolve(
+        host: &FakeHost,
+        lsp_settings: LspSettings,
+        settings: &OdinSettings,
+    ) -> Result<OlsBinary> {
+        let mut extension = <OdinExtension as zed::Extension>::new();
+        extension.language_server_binary(host, "ols", lsp_settings, settings)
+    }
+
+    #[test]
+    fn binary_path_setting_wins() {
+        let cache_dir = CacheDir::new("binary-path");
+        let host = FakeHost {
+            path: HashMap::from([("ols".to_string(), "/usr/bin/ols".to_string())]),
+            ..FakeHost::default()
+        };
+        let lsp_settings = LspSettings {
+            binary: Some(BinarySettings {
+                path: Some("/opt/ols/ols".to_string()),
+                arguments: Some(vec!["--stdio".to_string()]),
+            }),
+            ..LspSettings::default()
+        };
+        let binary = resolve(&host, lsp_settings, &settings(&cache_dir)).unwrap();
+        assert_eq!(binary.path, "/opt/ols/ols");
+        assert_eq!(binary.args, Some(vec!["--stdio".to_string()]));
+        assert_eq!(binary.source, BinarySource::Settings);
+        assert!(host.downloaded.borrow().is_empty());
+    }
+
+    #[test]
+    fn env_var_binary_comes_before_path() {
+        let cache_dir = CacheDir::new("env-var");
+        let host = FakeHost {
+            path: HashMap::from([("ols".to_string(), "/usr/bin/ols".to_string())]),
+            shell_env: vec![("OLS_PATH".to_string(), "/nix/store/ols/bin/ols".to_string())],
+            ..FakeHost::default()
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings(&cache_dir)).unwrap();
+        assert_eq!(binary.path, "/nix/store/ols/bin/ols");
+        assert_eq!(binary.source, BinarySource::Env);
+    }
+
+    #[test]
+    fn path_comes_before_downloads_unless_prefer_path_is_off() {
+        let cache_dir = CacheDir::new("path-vs-cache");
+        let installed = install(&cache_dir, "dev-2024-05", 0);
+        let host = FakeHost {
+            path: HashMap::from([("ols".to_string(), "/usr/bin/ols".to_string())]),
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![release("dev-2024-05", &[ASSET_NAME])],
+            )]),
+            ..FakeHost::default()
+        };
+        let mut settings = settings(&cache_dir);
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.path, "/usr/bin/ols");
+        assert_eq!(binary.source, BinarySource::Path);
+
+        settings.prefer_path = false;
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.path, installed);
+        assert_eq!(binary.version.as_deref(), Some("dev-2024-05"));
+        assert_eq!(binary.source, BinarySource::Cache);
+        assert!(host.downloaded.borrow().is_empty());
+    }
+
+    #[test]
+    fn resolution_order_is_walked_in_order() {
+        let cache_dir = CacheDir::new("resolution-order");
+        let installed = install(&cache_dir, "dev-2024-05", 0);
+        let host = FakeHost {
+            path: HashMap::from([("ols".to_string(), "/usr/bin/ols".to_string())]),
+            ..FakeHost::default()
+        };
+        let settings = OdinSettings {
+            resolution_order: Some(vec![BinarySource::Cache, BinarySource::Path]),
+            ..settings(&cache_dir)
+        };
+        let mut extension = <OdinExtension as zed::Extension>::new();
+        let binary = extension
+            .language_server_binary(&host, "ols", LspSettings::default(), &settings)
+            .unwrap();
+        assert_eq!(binary.path, installed);
+        assert_eq!(binary.source, BinarySource::Cache);
+        assert_eq!(extension.cached_binary_paths["ols"], installed);
+        assert!(host.downloaded.borrow().is_empty());
+
+        let settings = OdinSettings {
+            resolution_order: Some(vec![BinarySource::Path, BinarySource::Cache]),
+            ..settings
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.path, "/usr/bin/ols");
+        assert_eq!(binary.source, BinarySource::Path);
+    }
+
+    #[test]
+    fn resolution_order_without_download_can_find_nothing() {
+        let cache_dir = CacheDir::new("resolution-order-no-download");
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![release("dev-2024-05", &[ASSET_NAME])],
+            )]),
+            ..FakeHost::default()
+        };
+        let settings = OdinSettings {
+            resolution_order: Some(vec![BinarySource::Env, BinarySource::Path]),
+            ..settings(&cache_dir)
+        };
+        let err = resolve(&host, LspSettings::default(), &settings).unwrap_err();
+        assert!(
+            err.starts_with("no ols found from any source in resolution_order"),
+            "{err}"
+        );
+        assert!(host.downloaded.borrow().is_empty());
+    }
+
+    #[test]
+    fn version_manager_runs_the_ols_it_selects() {
+        let cache_dir = CacheDir::new("version-manager");
+        let host = FakeHost {
+            path: HashMap::from([
+                ("mise".to_string(), "/usr/bin/mise".to_string()),
+                ("ols".to_string(), "/usr/bin/ols".to_string()),
+            ]),
+            ..FakeHost::default()
+        };
+        let mut settings = OdinSettings {
+            version_manager: Some(VersionManager::Mise),
+            ..settings(&cache_dir)
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.path, "/usr/bin/mise");
+        assert_eq!(binary.args.unwrap(), ["exec", "--", "ols"]);
+        assert_eq!(binary.source, BinarySource::VersionManager);
+
+        // Without version_manager in it, a resolution_order doesn't use the manager
+        settings.resolution_order = Some(vec![BinarySource::Path]);
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.path, "/usr/bin/ols");
+        assert_eq!(binary.source, BinarySource::Path);
+    }
+
+    #[test]
+    fn path_min_version_must_be_a_release() {
+        let parse = |min_version: &str| {
+            OdinSettings::from_lsp_settings(&LspSettings {
+                settings: Some(serde_json::json!({ "path_min_version": min_version })),
+                ..LspSettings::default()
+            })
+        };
+        assert!(parse("dev-2024-05").is_ok());
+        for min_version in ["2024-05", "dev-2024-5", "v0.1.0", " dev-2024-05"] {
+            let err = parse(min_version).unwrap_err();
+            assert!(err.contains("path_min_version"), "{err}");
+        }
+    }
+
+    #[test]
+    fn path_binaries_older_than_path_min_version_are_skipped() {
+        let cache_dir = CacheDir::new("path-min-version");
+        let installed = install(&cache_dir, "dev-2024-05", now_secs().unwrap());
+        let mut host = FakeHost {
+            path: HashMap::from([("ols".to_string(), "/opt/ols-dev-2024-01/ols".to_string())]),
+            ..FakeHost::default()
+        };
+        let settings = OdinSettings {
+            prefer_path: true,
+            path_min_version: Some("dev-2024-03".to_string()),
+            update_check_interval: UpdateCheckInterval::Never,
+            ..settings(&cache_dir)
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.path, installed);
+
+        host.path
+            .insert("ols".to_string(), "/opt/ols-dev-2024-04/ols".to_string());
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.path, "/opt/ols-dev-2024-04/ols");
+    }
+
+    #[test]
+    fn pinned_version_uses_the_installed_release() {
+        let cache_dir = CacheDir::new("pinned-installed");
+        let pinned = install(&cache_dir, "dev-2024-05", 1);
+        install(&cache_dir, "dev-2024-06", 2);
+        let host = FakeHost {
+            files: HashMap::from([(OLS_VERSION_FILE.to_string(), "dev-2024-05\n".to_string())]),
+            ..FakeHost::default()
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings(&cache_dir)).unwrap();
+        assert_eq!(binary.path, pinned);
+        assert_eq!(binary.version.as_deref(), Some("dev-2024-05"));
+        assert!(host.downloaded.borrow().is_empty());
+    }
+
+    #[test]
+    fn pinned_version_is_downloaded_by_tag() {
+        let cache_dir = CacheDir::new("pinned-download");
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![
+                    release("dev-2024-06", &[ASSET_NAME]),
+                    release("dev-2024-05", &[ASSET_NAME]),
+                ],
+            )]),
+            downloads: HashMap::from([(
+                asset_url("dev-2024-05", ASSET_NAME),
+                ols_binary("dev-2024-05"),
+            )]),
+            ..FakeHost::default()
+        };
+        let settings = OdinSettings {
+            version: Some("dev-2024-05".to_string()),
+            ..settings(&cache_dir)
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(
+            binary.path,
+            format!("{cache_dir}/ols-dev-2024-05/{BINARY_NAME}")
+        );
+        assert_eq!(binary.version.as_deref(), Some("dev-2024-05"));
+        assert_eq!(
+            *host.downloaded.borrow(),
+            [
+                format!("https://api.github.com/repos/{REPOSITORY}/releases/tags/dev-2024-05"),
+                asset_url("dev-2024-05", ASSET_NAME),
+            ]
+        );
+    }
+
+    #[test]
+    fn latest_release_is_downloaded() {
+        let cache_dir = CacheDir::new("download");
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![release("dev-2024-05", &[ASSET_NAME])],
+            )]),
+            downloads: HashMap::from([(
+                asset_url("dev-2024-05", ASSET_NAME),
+                ols_binary("dev-2024-05"),
+            )]),
+            ..FakeHost::default()
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings(&cache_dir)).unwrap();
+        let version_dir = format!("{cache_dir}/ols-dev-2024-05");
+        assert_eq!(binary.path, format!("{version_dir}/{BINARY_NAME}"));
+        assert_eq!(binary.source, BinarySource::Download);
+        assert_eq!(
+            installed_version(&version_dir).as_deref(),
+            Some("dev-2024-05")
+        );
+        assert!(host
+            .statuses
+            .borrow()
+            .iter()
+            .any(|s| s.contains("Downloading")));
+    }
+
+    #[test]
+    fn newest_tested_release_is_installed_instead_of_an_untested_one() {
+        let (_, newest_tested) = TESTED_OLS_RELEASES;
+        let cache_dir = CacheDir::new("untested");
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![
+                    release("dev-2099-01", &[ASSET_NAME]),
+                    release(newest_tested, &["ols-x86_64-pc-windows-msvc.zip"]),
+                    release("dev-2024-06", &[ASSET_NAME]),
+                ],
+            )]),
+            downloads: HashMap::from([
+                (
+                    asset_url("dev-2099-01", ASSET_NAME),
+                    ols_binary("dev-2099-01"),
+                ),
+                (
+                    asset_url("dev-2024-06", ASSET_NAME),
+                    ols_binary("dev-2024-06"),
+                ),
+            ]),
+            ..FakeHost::default()
+        };
+        let mut settings = settings(&cache_dir);
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.version.as_deref(), Some("dev-2024-06"));
+
+        settings.allow_untested_versions = true;
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.version.as_deref(), Some("dev-2099-01"));
+    }
+
+    #[test]
+    fn missing_asset_is_an_error() {
+        let cache_dir = CacheDir::new("missing-asset");
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![release("dev-2024-05", &["ols-x86_64-pc-windows-msvc.zip"])],
+            )]),
+            ..FakeHost::default()
+        };
+        let err = resolve(&host, LspSettings::default(), &settings(&cache_dir)).unwrap_err();
+        assert!(err.starts_with("no asset found matching"), "{err}");
+        assert!(err.contains(ASSET_NAME), "{err}");
+        assert!(host.downloaded.borrow().is_empty());
+    }
+
+    #[test]
+    fn releases_come_from_the_first_repository_with_our_asset() {
+        let cache_dir = CacheDir::new("repositories");
+        let fork = "example/ols-fork";
+        let host = FakeHost {
+            releases: HashMap::from([
+                (
+                    REPOSITORY.to_string(),
+                    vec![release("dev-2024-05", &["ols-x86_64-pc-windows-msvc.zip"])],
+                ),
+                (
+                    fork.to_string(),
+                    vec![release("dev-2024-05", &[ASSET_NAME])],
+                ),
+            ]),
+            downloads: HashMap::from([(
+                asset_url("dev-2024-05", ASSET_NAME),
+                ols_binary("dev-2024-05"),
+            )]),
+            ..FakeHost::default()
+        };
+        let mut settings = OdinSettings {
+            repositories: Some(vec![REPOSITORY.to_string(), fork.to_string()]),
+            ..settings(&cache_dir)
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings).unwrap();
+        assert_eq!(binary.source, BinarySource::Download);
+        assert_eq!(binary.repository.as_deref(), Some(fork));
+
+        // A missing asset is blamed on the repository the release came from
+        let cache_dir = CacheDir::new("repositories-missing-asset");
+        let host = FakeHost {
+            releases: HashMap::from([(fork.to_string(), host.releases[REPOSITORY].clone())]),
+            ..FakeHost::default()
+        };
+        settings.repositories = Some(vec![fork.to_string()]);
+        settings.cache_dir = Some(cache_dir.to_string());
+        let err = resolve(&host, LspSettings::default(), &settings).unwrap_err();
+        assert!(err.contains(&format!("in {fork} release")), "{err}");
+        assert!(err.contains(&format!("https://github.com/{fork}")), "{err}");
+    }
+
+    #[test]
+    fn failed_download_falls_back_to_the_previous_release() {
+        let cache_dir = CacheDir::new("download-fallback");
+        let previous = install(&cache_dir, "dev-2024-05", 0);
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![release("dev-2024-06", &[ASSET_NAME])],
+            )]),
+            // A captive portal's login page instead of the archive
+            downloads: HashMap::from([(
+                asset_url("dev-2024-06", ASSET_NAME),
+                b"<html>log in to continue</html>".to_vec(),
+            )]),
+            ..FakeHost::default()
+        };
+        let binary = resolve(&host, LspSettings::default(), &settings(&cache_dir)).unwrap();
+        assert_eq!(binary.path, previous);
+        assert_eq!(binary.version.as_deref(), Some("dev-2024-05"));
+        assert!(fs::metadata(format!("{cache_dir}/ols-dev-2024-06")).is_err());
+    }
+
+    #[test]
+    fn select_release_keeps_the_installed_latest_release() {
+        let cache_dir = CacheDir::new("select-installed");
+        let installed = install(&cache_dir, "dev-2024-05", 0);
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![release("dev-2024-05", &[ASSET_NAME])],
+            )]),
+            ..FakeHost::default()
+        };
+        let settings = settings(&cache_dir);
+        let resolution = Resolution::new(&host, &settings, None, None);
+        let ReleaseChoice::Keep { binary, remember } =
+            resolution.select_release(&mut HashSet::new()).unwrap()
+        else {
+            panic!("the installed release should be kept");
+        };
+        assert_eq!(binary.path, installed);
+        assert_eq!(binary.repository.as_deref(), Some(REPOSITORY));
+        assert!(remember);
+    }
+
+    #[test]
+    fn select_release_picks_a_newer_release() {
+        let cache_dir = CacheDir::new("select-newer");
+        install(&cache_dir, "dev-2024-05", 0);
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![release("dev-2024-06", &[ASSET_NAME])],
+            )]),
+            ..FakeHost::default()
+        };
+        let settings = settings(&cache_dir);
+        let resolution = Resolution::new(&host, &settings, None, None);
+        let ReleaseChoice::Download(release, repository) =
+            resolution.select_release(&mut HashSet::new()).unwrap()
+        else {
+            panic!("the newer release should be downloaded");
+        };
+        assert_eq!(release.version, "dev-2024-06");
+        assert_eq!(repository, REPOSITORY);
+        assert!(host.downloaded.borrow().is_empty());
+    }
+
+    #[test]
+    fn install_only_downloads_what_is_missing() {
+        let cache_dir = CacheDir::new("install");
+        let host = FakeHost {
+            downloads: HashMap::from([(
+                asset_url("dev-2024-05", ASSET_NAME),
+                ols_binary("dev-2024-05"),
+            )]),
+            ..FakeHost::default()
+        };
+        let settings = settings(&cache_dir);
+        let resolution = Resolution::new(&host, &settings, None, None);
+        let (release, _) = release("dev-2024-05", &[ASSET_NAME]);
+        for expect_download in [true, false] {
+            let Install::Release {
+                version_dir,
+                binary_path,
+                downloaded,
+                ..
+            } = resolution.install(&release, REPOSITORY).unwrap()
+            else {
+                panic!("the release should be installed");
+            };
+            assert_eq!(version_dir, format!("{cache_dir}/ols-dev-2024-05"));
+            assert_eq!(binary_path, format!("{version_dir}/{BINARY_NAME}"));
+            assert_eq!(downloaded, expect_download);
+        }
+        assert_eq!(host.downloaded.borrow().len(), 1);
+    }
+
+    #[test]
+    fn clean_up_keeps_the_current_version() {
+        let cache_dir = CacheDir::new("clean-up");
+        install(&cache_dir, "dev-2024-05", 0);
+        install(&cache_dir, "dev-2024-06", 0);
+        let marker = cache_path(Some(&cache_dir), CACHE_FORMAT_FILE);
+        write_state(&marker, CACHE_FORMAT_VERSION).unwrap();
+        let host = FakeHost::default();
+        let settings = settings(&cache_dir);
+        let resolution = Resolution::new(&host, &settings, None, None);
+        let version_dir = format!("{cache_dir}/ols-dev-2024-06");
+        resolution
+            .clean_up("ols-dev-2024-06", &version_dir)
+            .unwrap();
+        assert!(fs::metadata(format!("{cache_dir}/ols-dev-2024-05")).is_err());
+        assert!(fs::metadata(&version_dir).is_ok());
+        assert!(fs::metadata(&marker).is_ok());
+    }
+
+    #[test]
+    fn oversized_download_is_deleted() {
+        let cache_dir = CacheDir::new("oversized");
+        let host = FakeHost {
+            releases: HashMap::from([(
+                REPOSITORY.to_string(),
+                vec![release("dev-2024-05", &[ASSET_NAME])],
+            )]),
+            downloads: HashMap::from([(
+                asset_url("dev-2024-05", ASSET_NAME),
+                ols_binary("dev-2024-05"),
+            )]),
+            ..FakeHost::default()
+        };
+        let settings = OdinSettings {
+            max_download_size_mb: 0,
+            ..settings(&cache_dir)
+        };
+        let err = resolve(&host, LspSettings::default(), &settings).unwrap_err();
+        assert!(err.contains("larger than max_download_size_mb"), "{err}");
+        assert!(err.ends_with("the download has been deleted"), "{err}");
+        assert!(fs::metadata(format!("{cache_dir}/ols-dev-2024-05")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn disk_usage_skips_links() {
+        let cache_dir = CacheDir::new("disk-usage");
+        fs::write(format!("{cache_dir}/ols"), [0; 10]).unwrap();
+        std::os::unix::fs::symlink(&*cache_dir, format!("{cache_dir}/loop")).unwrap();
+        std::os::unix::fs::symlink("/", format!("{cache_dir}/root")).unwrap();
+        assert_eq!(disk_usage(Path::new(&*cache_dir)), 10);
+    }
+
+    #[test]
+    fn root_patterns_match_paths() {
+        for (pattern, path, matches) in [
+            ("/work/engine", "/work/engine", true),
+            ("/work/engine/", "/work/engine/src", true),
+            ("/work/engine", "/work/engine2", false),
+            ("/work/engine", "/work", false),
+            ("/work/*", "/work/engine", true),
+            ("/work/*", "/work/engine/src", false),
+            ("/work/*/src", "/work/engine/src", true),
+            ("/work/**", "/work/engine/src", true),
+            ("**/src", "/work/engine/src", true),
+            ("**/src", "/work/engine/src2", false),
+            ("/work/engine?", "/work/engine2", true),
+            ("/work/engine?", "/work/engine", false),
+            ("/work?engine", "/work/engine", false),
+        ] {
+            assert_eq!(
+                matches_root_pattern(pattern, path),
+                matches,
+                "{pattern} against {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn update_checks_follow_the_interval() {
+        use UpdateCheckInterval::{Daily, Never, Startup};
+        let cache_dir = CacheDir::new("update-check");
+        let version_dir = format!("{cache_dir}/ols-dev-2024-05");
+        let path = format!("{version_dir}/{LAST_UPDATE_CHECK_FILE}");
+        let now = now_secs().unwrap();
+        // Seconds since the last check, or None for no recorded check
+        for (interval, since_check, due) in [
+            (Never, Some(now), false),
+            (Never, None, false),
+            (Startup, Some(0), true),
+            (Daily, Some(0), false),
+            (Daily, Some(SECONDS_PER_DAY - 60), false),
+            (Daily, Some(SECONDS_PER_DAY), true),
+            (Daily, None, true),
+        ] {
+            install(
+                &cache_dir,
+                "dev-2024-05",
+                now - since_check.unwrap_or_default(),
+            );
+            if since_check.is_none() {
+                fs::remove_file(&path).unwrap();
+            }
+            assert_eq!(
+                update_check_due(interval, &version_dir),
+                due,
+                "{interval:?} {since_check:?}s after the last check"
+            );
+        }
+
+        // A corrupt timestamp counts as no check and is discarded
+        fs::write(&path, "yesterday").unwrap();
+        assert!(update_check_due(Daily, &version_dir));
+        assert!(fs::metadata(&path).is_err());
+    }
+
+    #[test]
+    fn version_dir_suffixes_are_path_safe() {
+        let url = "https://example.com/ols.zip";
+        for (version, suffix) in [
+            ("dev-2024-05", "dev-2024-05".to_string()),
+            ("v0.1.0-rc.1", "v0.1.0-rc.1".to_string()),
+            (" dev-2024-05\n", "dev-2024-05".to_string()),
+            ("fork/dev 2024:05", "fork_dev_2024_05".to_string()),
+            ("../..", format!("{:016x}", stable_hash(url))),
+            ("", format!("{:016x}", stable_hash(url))),
+            ("..", format!("{:016x}", stable_hash(url))),
+            ("///", format!("{:016x}", stable_hash(url))),
+        ] {
+            assert_eq!(version_dir_suffix(version, url), suffix, "{version:?}");
+        }
+    }
+
+    #[test]
+    fn only_needed_variables_are_forwarded() {
+        let settings = OdinSettings {
+            binary_env_var: "MY_OLS".to_string(),
+            ..OdinSettings::default()
+        };
+        for (key, forwarded) in [
+            ("PATH", true),
+            ("HOME", true),
+            ("ODIN_ROOT", true),
+            ("OLS_LOG", true),
+            ("LC_ALL", true),
+            ("XDG_CONFIG_HOME", true),
+            ("SSL_CERT_FILE", true),
+            ("HTTPS_PROXY", true),
+            ("https_proxy", true),
+            ("MY_OLS", true),
+            ("AWS_SECRET_ACCESS_KEY", false),
+            ("GITHUB_TOKEN", false),
+            ("PATHEXT", false),
+            ("path", false),
+        ] {
+            assert_eq!(is_forwarded_var(key, &settings), forwarded, "{key}");
+        }
+    }
+
+    fn elf_header(machine: u16) -> Vec<u8> {
+        let mut header = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0];
+        header.resize(18, 0);
+        header.extend(machine.to_le_bytes());
+        header.resize(64, 0);
+        header
+    }
+
+    #[test]
+    fn rate_limits_are_only_retried_with_nothing_installed() {
+        for (nothing_installed, attempts) in [(false, 1), (true, 2)] {
+            let attempted = std::cell::Cell::new(0);
+            let result: Result<()> = retry_github_request(nothing_installed, || {
+                attempted.set(attempted.get() + 1);
+                Err("status error 403: API rate limit exceeded".to_string())
+            });
+            assert!(result.is_err());
+            assert_eq!(
+                attempted.get(),
+                attempts,
+                "nothing installed: {nothing_installed}"
+            );
+        }
+    }
+
+    #[test]
+    fn only_parse_failures_are_malformed_responses() {
+        for err in [
+            "malformed response from https://api.github.com/repos/a/b: expected value at line 1",
+            "error deserializing latest release: EOF while parsing",
+        ] {
+            assert!(is_malformed_response(err), "{err}");
+        }
+        for err in [
+            "failed to read github-api-0123456789abcdef.json: No such file or directory",
+            "status error 404 downloading https://api.github.com/repos/a/b/releases/tags/x.json",
+        ] {
+            assert!(!is_malformed_response(err), "{err}");
+        }
+    }
+
+    #[test]
+    fn semver_precedence_orders_pre_releases() {
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "v1.0.0",
+            "1.0.1+build.5",
+            "1.10.0",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(
+                semver_cmp(pair[0], pair[1]),
+                Some(Ordering::Less),
+                "{pair:?}"
+            );
+            assert_eq!(
+                semver_cmp(pair[1], pair[0]),
+                Some(Ordering::Greater),
+                "{pair:?}"
+            );
+        }
+        assert_eq!(semver_cmp("1.0.0+a", "1.0.0+b"), Some(Ordering::Equal));
+        assert_eq!(semver_cmp("dev-2024-04", "1.0.0"), None);
+        assert_eq!(semver_cmp("nightly", "nightly"), None);
+        assert_eq!(semver_cmp("1.0", "1.0.0"), None);
+    }
+
+    #[test]
+    fn elf_machine_must_match_arch() {
+        let header = elf_header(0x3e);
+        assert!(is_executable_header(
+            &header,
+            zed::Os::Linux,
+            zed::Architecture::X8664
+        ));
+        assert!(!is_executable_header(
+            &header,
+            zed::Os::Linux,
+            zed::Architecture::Aarch64
+        ));
+        assert!(!is_executable_header(
+            &header,
+            zed::Os::Mac,
+            zed::Architecture::X8664
+        ));
+        assert!(is_executable_header(
+            &elf_header(0xb7),
+            zed::Os::Linux,
+            zed::Architecture::Aarch64
+        ));
+    }
+
+    #[test]
+    fn mach_o_cpu_type_must_match_arch() {
+        let mut header = vec![0xcf, 0xfa, 0xed, 0xfe];
+        header.extend(0x0100_000c_u32.to_le_bytes());
+        assert!(is_executable_header(
+            &header,
+            zed::Os::Mac,
+            zed::Architecture::Aarch64
+        ));
+        assert!(!is_executable_header(
+            &header,
+            zed::Os::Mac,
+            zed::Architecture::X8664
+        ));
+    }
+
+    #[test]
+    fn universal_binary_matches_any_slice() {
+        let mut header = vec![0xca, 0xfe, 0xba, 0xbe];
+        header.extend(2_u32.to_be_bytes());
+        for cpu_type in [0x0100_0007_u32, 0x0100_000c] {
+            header.extend(cpu_type.to_be_bytes());
+            header.extend([0; 16]);
+        }
+        assert!(is_executable_header(
+            &header,
+            zed::Os::Mac,
+            zed::Architecture::X8664
+        ));
+        assert!(is_executable_header(
+            &header,
+            zed::Os::Mac,
+            zed::Architecture::Aarch64
+        ));
+        assert!(!is_executable_header(
+            &header,
+            zed::Os::Mac,
+            zed::Architecture::X86
+        ));
+    }
+
+    #[test]
+    fn pe_machine_must_match_arch() {
+        let mut header = b"MZ".to_vec();
+        header.resize(0x3c, 0);
+        header.extend(0x80_u32.to_le_bytes());
+        header.resize(0x80, 0);
+        header.extend(b"PE\0\0");
+        header.extend(0x8664_u16.to_le_bytes());
+        assert!(is_executable_header(
+            &header,
+            zed::Os::Windows,
+            zed::Architecture::X8664
+        ));
+        assert!(!is_executable_header(
+            &header,
+            zed::Os::Windows,
+            zed::Architecture::Aarch64
+        ));
+    }
+
+    #[test]
+    fn truncated_headers_are_not_executables() {
+        assert!(!is_executable_header(
+            b"\x7fELF",
+            zed::Os::Linux,
+            zed::Architecture::X8664
+        ));
+        assert!(!is_executable_header(
+            b"MZ",
+            zed::Os::Windows,
+            zed::Architecture::X8664
+        ));
+        assert!(!is_executable_header(
+            &[],
+            zed::Os::Mac,
+            zed::Architecture::Aarch64
+        ));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{github, host::Host, server::LanguageServer, settings::Channel};
+use zed_extension_api::{self as zed, Result};
+
+/// Returns the release notes of the latest release on `channel` and of the `installed` one in
+/// `repository`, for deciding whether to update or pin. Nothing is downloaded but the notes
+/// themselves.
+pub fn release_notes(
+    host: &impl Host,
+    server: &dyn LanguageServer,
+    repository: &str,
+    channel: Channel,
+    installed: Option<&str>,
+) -> String {
+    let release = host.latest_github_release(
+        repository,
+        zed::GithubReleaseOptions {
+            require_assets: false,
+            pre_release: channel == Channel::Prerelease,
+        },
+    );
+    let latest = match release {
+        Ok(release) => release.version,
+        Err(err) => {
+            return format!(
+                "could not look up the latest {} release: {err}",
+                server.name()
+            );
+        }
+    };
+    let versions = match installed {
+        Some(installed) if installed == latest => vec![("latest and installed", latest.as_str())],
+        Some(installed) => vec![("latest", latest.as_str()), ("installed", installed)],
+        None => vec![("latest", latest.as_str())],
+    };
+    versions
+        .into_iter()
+        .map(|(which, version)| {
+            let notes = notes(host, repository, version)
+                .unwrap_or_else(|err| format!("(unavailable: {err})"));
+            format!("{} {version} ({which}):\n{}", server.name(), notes.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Fetches the body of the release tagged `version`, which the extension API's releases lack,
+/// from the GitHub API.
+fn notes(host: &impl Host, repository: &str, version: &str) -> Result<String> {
+    let url = format!("https://api.github.com/repos/{repository}/releases/tags/{version}");
+    Ok(github::fetch_json(host, &url)?
+        .get("body")
+        .and_then(|body| body.as_str())
+        .filter(|body| !body.trim().is_empty())
+        .unwrap_or("(no release notes)")
+        .to_string())
+}
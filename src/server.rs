@@ -0,0 +1,289 @@
+use serde::Deserialize;
+use zed_extension_api as zed;
+
+/// A language server the extension knows how to locate and download.
+pub trait LanguageServer {
+    /// Name of the executable looked up on PATH, also used to prefix version directories.
+    fn name(&self) -> &'static str;
+
+    /// GitHub repository releases are downloaded from, as `owner/repo`.
+    fn repository(&self) -> &'static str;
+
+    /// Name of the executable inside a downloaded release.
+    fn binary_name(&self, platform: zed::Os, arch: zed::Architecture) -> String;
+
+    /// Names the executable may actually have inside a downloaded release, most specific first,
+    /// since upstream packaging doesn't always use [`LanguageServer::binary_name`].
+    fn binary_names(&self, platform: zed::Os, arch: zed::Architecture) -> Vec<String> {
+        // A release matched through a platform alias names its executable the same way
+        let mut names = vec![self.binary_name(platform, arch)];
+        for stem in self.asset_stems(platform, arch) {
+            if !names.contains(&stem) {
+                names.push(stem);
+            }
+        }
+        names.push(self.name().to_string());
+        match platform {
+            zed::Os::Windows => names
+                .into_iter()
+                .flat_map(|name| [format!("{name}.exe"), name])
+                .collect(),
+            zed::Os::Mac | zed::Os::Linux => names,
+        }
+    }
+
+    /// Name of the release asset to download, without its archive extension.
+    fn asset_stem(&self, platform: zed::Os, arch: zed::Architecture) -> String;
+
+    /// Every asset name the server may be published under, most likely first, for forks that
+    /// spell platforms differently. Defaults to just [`LanguageServer::asset_stem`].
+    fn asset_stems(&self, platform: zed::Os, arch: zed::Architecture) -> Vec<String> {
+        vec![self.asset_stem(platform, arch)]
+    }
+
+    /// Archive format the server usually publishes for the platform.
+    fn archive_format(&self, platform: zed::Os) -> ArchiveFormat;
+}
+
+/// The archive formats a release asset can be downloaded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ArchiveFormat {
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz", alias = "gzip_tar")]
+    TarGz,
+    #[serde(rename = "gz", alias = "gzip")]
+    Gz,
+    /// The asset is the executable itself.
+    #[serde(rename = "none", alias = "uncompressed")]
+    Uncompressed,
+}
+
+impl ArchiveFormat {
+    pub const ALL: [ArchiveFormat; 4] = [
+        ArchiveFormat::Zip,
+        ArchiveFormat::TarGz,
+        ArchiveFormat::Gz,
+        ArchiveFormat::Uncompressed,
+    ];
+
+    /// Returns the asset name for an asset of this format named `stem`.
+    pub fn asset_name(self, stem: &str) -> String {
+        match self {
+            ArchiveFormat::Zip => format!("{stem}.zip"),
+            ArchiveFormat::TarGz => format!("{stem}.tar.gz"),
+            ArchiveFormat::Gz => format!("{stem}.gz"),
+            ArchiveFormat::Uncompressed => stem.to_string(),
+        }
+    }
+
+    pub fn downloaded_file_type(self) -> zed::DownloadedFileType {
+        match self {
+            ArchiveFormat::Zip => zed::DownloadedFileType::Zip,
+            ArchiveFormat::TarGz => zed::DownloadedFileType::GzipTar,
+            ArchiveFormat::Gz => zed::DownloadedFileType::Gzip,
+            ArchiveFormat::Uncompressed => zed::DownloadedFileType::Uncompressed,
+        }
+    }
+
+    /// Whether the download is extracted into a directory, rather than written as a single file.
+    pub fn extracts_to_directory(self) -> bool {
+        matches!(self, ArchiveFormat::Zip | ArchiveFormat::TarGz)
+    }
+}
+
+/// The language server selected by the `server` setting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Server {
+    #[default]
+    Ols,
+}
+
+impl Server {
+    pub fn implementation(self) -> &'static dyn LanguageServer {
+        match self {
+            Server::Ols => &Ols,
+        }
+    }
+}
+
+/// [ols](https://github.com/DanielGavin/ols), the Odin Language Server.
+pub struct Ols;
+
+impl LanguageServer for Ols {
+    fn name(&self) -> &'static str {
+        "ols"
+    }
+
+    fn repository(&self) -> &'static str {
+        "DanielGavin/ols"
+    }
+
+    fn binary_name(&self, platform: zed::Os, arch: zed::Architecture) -> String {
+        format!(
+            "ols-{}-{}",
+            ols_arch(platform, arch),
+            ols_os_names(platform)[0]
+        )
+    }
+
+    fn asset_stem(&self, platform: zed::Os, arch: zed::Architecture) -> String {
+        self.binary_name(platform, arch)
+    }
+
+    fn asset_stems(&self, platform: zed::Os, arch: zed::Architecture) -> Vec<String> {
+        let arch = ols_arch(platform, arch);
+        ols_os_names(platform)
+            .iter()
+            .map(|os| format!("ols-{arch}-{os}"))
+            .collect()
+    }
+
+    fn archive_format(&self, platform: zed::Os) -> ArchiveFormat {
+        match platform {
+            zed::Os::Mac | zed::Os::Linux => ArchiveFormat::Zip,
+            zed::Os::Windows => ArchiveFormat::Zip,
+        }
+    }
+}
+
+/// Architecture as spelled in ols asset names.
+fn ols_arch(platform: zed::Os, arch: zed::Architecture) -> &'static str {
+    match (platform, arch) {
+        (_, zed::Architecture::Aarch64) => "arm64",
+        // macOS has been 64-bit only since 10.15 and no 32-bit darwin ols exists, so a reported
+        // X86 can only be an x86_64 machine
+        (zed::Os::Mac, zed::Architecture::X86) => "x86_64",
+        (_, zed::Architecture::X86) => "x86",
+        (_, zed::Architecture::X8664) => "x86_64",
+    }
+}
+
+/// Spellings of each OS in ols asset names: upstream's first, then ones forks are known to use.
+/// Extend this list when a fork turns up another one.
+fn ols_os_names(platform: zed::Os) -> &'static [&'static str] {
+    match platform {
+        zed::Os::Mac => &["darwin", "apple-darwin", "macos"],
+        zed::Os::Linux => &["unknown-linux-gnu", "linux-gnu", "linux"],
+        zed::Os::Windows => &["pc-windows-msvc", "windows-msvc", "windows"],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zed::{Architecture, Os};
+
+    #[test]
+    fn ols_arch_spells_ols_asset_names() {
+        for (platform, arch, expected) in [
+            (Os::Mac, Architecture::Aarch64, "arm64"),
+            (Os::Mac, Architecture::X8664, "x86_64"),
+            // No 32-bit darwin ols exists
+            (Os::Mac, Architecture::X86, "x86_64"),
+            (Os::Linux, Architecture::Aarch64, "arm64"),
+            (Os::Linux, Architecture::X8664, "x86_64"),
+            (Os::Linux, Architecture::X86, "x86"),
+            (Os::Windows, Architecture::X8664, "x86_64"),
+            (Os::Windows, Architecture::X86, "x86"),
+        ] {
+            assert_eq!(ols_arch(platform, arch), expected, "{platform:?} {arch:?}");
+        }
+    }
+
+    #[test]
+    fn asset_stems_try_every_platform_alias() {
+        for (platform, arch, expected) in [
+            (
+                Os::Mac,
+                Architecture::Aarch64,
+                &[
+                    "ols-arm64-darwin",
+                    "ols-arm64-apple-darwin",
+                    "ols-arm64-macos",
+                ],
+            ),
+            (
+                Os::Mac,
+                Architecture::X86,
+                &[
+                    "ols-x86_64-darwin",
+                    "ols-x86_64-apple-darwin",
+                    "ols-x86_64-macos",
+                ],
+            ),
+            (
+                Os::Linux,
+                Architecture::X8664,
+                &[
+                    "ols-x86_64-unknown-linux-gnu",
+                    "ols-x86_64-linux-gnu",
+                    "ols-x86_64-linux",
+                ],
+            ),
+            (
+                Os::Windows,
+                Architecture::X86,
+                &[
+                    "ols-x86-pc-windows-msvc",
+                    "ols-x86-windows-msvc",
+                    "ols-x86-windows",
+                ],
+            ),
+        ] {
+            assert_eq!(
+                Ols.asset_stems(platform, arch),
+                expected,
+                "{platform:?} {arch:?}"
+            );
+            assert_eq!(Ols.asset_stem(platform, arch), expected[0]);
+        }
+    }
+
+    #[test]
+    fn binary_names_cover_aliases_and_the_plain_name() {
+        for (platform, arch, expected) in [
+            (
+                Os::Linux,
+                Architecture::Aarch64,
+                vec![
+                    "ols-arm64-unknown-linux-gnu",
+                    "ols-arm64-linux-gnu",
+                    "ols-arm64-linux",
+                    "ols",
+                ],
+            ),
+            (
+                Os::Mac,
+                Architecture::X86,
+                vec![
+                    "ols-x86_64-darwin",
+                    "ols-x86_64-apple-darwin",
+                    "ols-x86_64-macos",
+                    "ols",
+                ],
+            ),
+            (
+                Os::Windows,
+                Architecture::X8664,
+                vec![
+                    "ols-x86_64-pc-windows-msvc.exe",
+                    "ols-x86_64-pc-windows-msvc",
+                    "ols-x86_64-windows-msvc.exe",
+                    "ols-x86_64-windows-msvc",
+                    "ols-x86_64-windows.exe",
+                    "ols-x86_64-windows",
+                    "ols.exe",
+                    "ols",
+                ],
+            ),
+        ] {
+            assert_eq!(
+                Ols.binary_names(platform, arch),
+                expected,
+                "{platform:?} {arch:?}"
+            );
+        }
+    }
+}
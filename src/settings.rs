@@ -0,0 +1,383 @@
+use crate::server::{ArchiveFormat, Server};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use zed_extension_api::{self as zed, serde_json, settings::LspSettings, Result};
+
+/// Settings for the extension itself, read from the `settings` object of the `ols` LSP settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OdinSettings {
+    /// Which language server to locate and download.
+    pub server: Server,
+    /// When to check GitHub for a newer ols release if one has already been downloaded.
+    pub update_check_interval: UpdateCheckInterval,
+    /// Whether an ols found on PATH takes precedence over a downloaded one.
+    pub prefer_path: bool,
+    /// Deletes all downloaded ols versions once, the next time ols starts after this is enabled.
+    pub clear_cache: bool,
+    /// Directory to download ols into instead of the extension's working directory.
+    pub cache_dir: Option<String>,
+    /// Worktree roots ols may start in, as path prefixes or globs. Unset means every worktree.
+    pub root_patterns: Option<Vec<String>>,
+    /// Archive format to download per OS (`"macos"`, `"linux"`, `"windows"`). Platforms without
+    /// an entry use whichever supported format the release actually has.
+    pub archive_formats: HashMap<String, ArchiveFormat>,
+    /// Path of an `ols.json`-style config file, relative to the worktree root, whose contents are
+    /// sent to ols as initialization options.
+    pub config_path: Option<String>,
+    /// Whether ols is started with the worktree's shell environment on macOS and Linux. Without
+    /// it, ols only gets `env` on top of Zed's own environment, which avoids spawning a shell.
+    pub inherit_shell_env: bool,
+    /// Environment variables to set for ols, overriding the shell environment.
+    pub env: BTreeMap<String, String>,
+    /// Shell command to run, with the same environment as ols, before ols starts. ols is not
+    /// started if it fails.
+    pub pre_start_command: Option<String>,
+    /// ols release to download instead of the latest one; an ols on PATH is not used while a
+    /// version is pinned. Takes precedence over a project's `.ols-version` file.
+    pub version: Option<String>,
+    /// Release channel to download ols from; stable unless nightly builds are opted into.
+    pub channel: Channel,
+    /// Whether the stable channel may fall back to a pre-release when no stable release has an
+    /// asset for this platform.
+    pub channel_fallback: bool,
+    /// Log every step of resolving and installing ols, and the effective settings with `env` values
+    /// hidden, for diagnosing install problems.
+    pub debug_install: bool,
+    /// Send ols a conservative profile that disables analyses known to crash on problematic
+    /// code. See `init_options::safe_mode_options` for exactly what is disabled.
+    pub safe_mode: bool,
+    /// When `ODIN_ROOT` isn't set, point it at the directory holding the `odin` found on PATH so
+    /// ols can resolve `core:` imports.
+    pub set_odin_root: bool,
+    /// Extra arguments for ols per OS (`"macos"`, `"linux"`, `"windows"`), passed before the
+    /// `binary.arguments` from the LSP settings.
+    pub platform_args: HashMap<String, Vec<String>>,
+    /// Environment variable that, when set, names the ols binary to use. Only `binary.path`
+    /// takes precedence over it.
+    pub binary_env_var: String,
+    /// Update to releases newer than the range the extension was tested against, instead of
+    /// staying on an already downloaded tested release or installing the newest tested one.
+    pub allow_untested_versions: bool,
+    /// Whether the extension removes old downloads after installing a new release and acts on
+    /// `clear_cache`. When off, binaries are only ever added.
+    pub manage_cache: bool,
+    /// Largest download, in megabytes, that is kept. Anything bigger is deleted again, which
+    /// guards against a misconfigured mirror serving something other than ols.
+    pub max_download_size_mb: u64,
+    /// Seconds `pre_start_command` may run before it is stopped and ols is not started; 0 waits
+    /// indefinitely.
+    pub startup_timeout_secs: u64,
+    /// How much of each completion's detail is shown in its label.
+    pub completion_detail: CompletionDetail,
+    /// Command and arguments to run ols under (a profiler, `strace`, a sandbox), with the ols
+    /// path and arguments appended.
+    pub wrapper: Option<Vec<String>>,
+    /// Highlight names to use in completion and symbol labels instead of the grammar's, keyed by
+    /// `"function"`, `"type"`, `"field"`, `"variable"`, `"module"`, `"constant"`, `"keyword"`,
+    /// `"operator"`, `"package"` or `"detail"`.
+    pub label_highlights: HashMap<String, String>,
+    /// Write a diagnostic report to the log and to `health-report.txt` in the extension's work
+    /// directory each time ols starts.
+    pub health_check: bool,
+    /// `owner/repo` GitHub repositories to download ols from, tried in order until one has a
+    /// release with an asset for this platform. Defaults to the upstream ols repository.
+    pub repositories: Option<Vec<String>>,
+    /// Odin release (e.g. `dev-2024-04`) the project is built with. ols is not started when a
+    /// different Odin is detected. Takes precedence over a project's `.odin-version` file; pair
+    /// it with `version` to lock both tools.
+    pub odin_version: Option<String>,
+    /// URL of a team's standard `ols.json`, sent as the base initialization options for
+    /// worktrees without an `ols.json` of their own. Downloaded once per URL.
+    pub ols_json_template_url: Option<String>,
+    /// Pass ols the whole shell environment instead of just the variables it may need (search
+    /// paths, locale, proxies, `ODIN_*` and `OLS_*`). `env` is always passed.
+    pub forward_full_env: bool,
+    /// Run ols with everything but diagnostics turned off, for low-resource machines. See
+    /// `init_options::check_only_options` for what is disabled.
+    pub check_only: bool,
+    /// Where to look for ols, in order; the first source that has one wins and sources left out
+    /// are never used. Without it: `binary.path`, `binary_env_var`, `version_manager`, then PATH
+    /// or downloads as `prefer_path` decides.
+    pub resolution_order: Option<Vec<BinarySource>>,
+    /// ols options the extension doesn't know about yet, e.g. new feature flags, sent to ols
+    /// as-is. They go in at the top level, where ols reads all its options, and aren't checked
+    /// against the known ones.
+    pub experimental: serde_json::Map<String, serde_json::Value>,
+    /// Name to look for on PATH instead of `ols`, e.g. `ols-nightly`. On Windows `.exe` may be
+    /// left off.
+    pub binary_name: Option<String>,
+    /// Starts ols, and `pre_start_command`, in the worktree root, which is also passed as
+    /// `ZED_WORKTREE_ROOT`. Needs `sh`, so it isn't available on Windows.
+    pub run_in_worktree_root: bool,
+    /// How to extract the downloaded asset (`"zip"`, `"gzip"`, `"gzip_tar"`, `"uncompressed"`,
+    /// or the `archive_formats` names), whatever its name suggests. Unlike `archive_formats`, it
+    /// doesn't change which asset is picked.
+    pub archive_type: Option<ArchiveFormat>,
+    /// Version manager (`"mise"` or `"asdf"`) to run ols through, so the version it selects for
+    /// the project is used. Takes precedence over PATH and downloads. Managers pick the version
+    /// from the working directory, so pair it with `run_in_worktree_root`.
+    pub version_manager: Option<VersionManager>,
+    /// After a download, also deletes directories in the extension's working directory that
+    /// aren't ols versions, such as leftovers of older releases of the extension. Off by default,
+    /// when only old `ols-*` versions are deleted.
+    pub aggressive_cleanup: bool,
+    /// When ols type-checks a file: on every `"change"`, or only on `"save"`, which is lighter
+    /// on slow machines. Unset leaves it to ols and `ols.json`.
+    pub check_on: Option<CheckOn>,
+    /// Shows the package a completion comes from in front of its name (`fmt.println`), when ols
+    /// names it in the completion's detail.
+    pub completion_package_prefix: bool,
+    /// Any ols options, merged into the initialization options last, so on conflict they override
+    /// everything else, `initialization_options` and the dedicated settings included.
+    pub raw_initialization_options: Option<serde_json::Value>,
+    /// Checks that a freshly downloaded ols contains the version of the release it came from,
+    /// downloading it once more if it doesn't and failing if it still doesn't.
+    pub verify_version: bool,
+    /// An already downloaded ols release to use, without checking for or downloading anything.
+    /// If it isn't installed, the installed ones are logged and ols is resolved as usual. Older
+    /// versions are only kept around with `manage_cache` off.
+    pub use_installed_version: Option<String>,
+    /// Starts ols with only `env`, plus `PATH` and `ODIN_ROOT` from the worktree's shell, instead
+    /// of the shell environment, for launches that don't depend on what the shell exports. Zed's
+    /// own environment still applies underneath.
+    pub clean_env: bool,
+    /// Oldest `dev-YYYY-MM` release of an ols on PATH to use; older ones are skipped in favor of
+    /// downloads, and any other form is rejected. Only ols installs whose path names their
+    /// release can be checked.
+    pub path_min_version: Option<String>,
+    /// Whether ols may be downloaded or updated when it starts. Off, only already downloaded
+    /// versions are used until it is turned back on, e.g. to keep large downloads off metered
+    /// connections.
+    pub download_on_startup: bool,
+    /// Odin `-define:` values, by name, that ols checks the code with. They're added to ols's
+    /// `checker_args`.
+    pub defines: BTreeMap<String, String>,
+    /// Odin target (`-target:`) ols checks the code for, e.g. `linux_arm64` when cross-compiling.
+    /// It only affects checking: the ols that is downloaded always runs on this machine.
+    pub target: Option<String>,
+    /// Whether ols answers find-references, which is expensive on large codebases. Unset leaves
+    /// it to ols and `ols.json`.
+    pub references: Option<bool>,
+    /// Logs every ols found (settings, environment, version manager, PATH and downloads) when
+    /// there is more than one, to make it clear which could have been used.
+    pub list_candidates: bool,
+    /// Days a superseded ols version is kept after it was last started, to go back to if a new
+    /// release misbehaves; it is removed by the first download after that. 0 removes it as soon
+    /// as a newer one is downloaded.
+    pub cleanup_grace_days: u64,
+    /// Checks whether GitHub can be reached when a release lookup or download fails, so the
+    /// error can say there is no network rather than looking like a problem with ols.
+    pub check_connectivity: bool,
+    /// Logs the release notes of the latest release on `channel` and of the installed version
+    /// when ols starts, to decide on updating or pinning.
+    pub release_notes: bool,
+}
+
+impl Default for OdinSettings {
+    fn default() -> Self {
+        Self {
+            server: Server::default(),
+            update_check_interval: UpdateCheckInterval::default(),
+            prefer_path: true,
+            clear_cache: false,
+            cache_dir: None,
+            root_patterns: None,
+            archive_formats: HashMap::new(),
+            config_path: None,
+            inherit_shell_env: true,
+            env: BTreeMap::new(),
+            pre_start_command: None,
+            version: None,
+            channel: Channel::default(),
+            channel_fallback: false,
+            debug_install: false,
+            safe_mode: false,
+            set_odin_root: false,
+            platform_args: HashMap::new(),
+            binary_env_var: "OLS_PATH".to_string(),
+            allow_untested_versions: false,
+            manage_cache: true,
+            max_download_size_mb: 512,
+            startup_timeout_secs: 60,
+            completion_detail: CompletionDetail::default(),
+            wrapper: None,
+            label_highlights: HashMap::new(),
+            health_check: false,
+            repositories: None,
+            odin_version: None,
+            ols_json_template_url: None,
+            forward_full_env: false,
+            check_only: false,
+            resolution_order: None,
+            experimental: serde_json::Map::new(),
+            binary_name: None,
+            run_in_worktree_root: false,
+            archive_type: None,
+            version_manager: None,
+            aggressive_cleanup: false,
+            check_on: None,
+            completion_package_prefix: false,
+            raw_initialization_options: None,
+            verify_version: false,
+            use_installed_version: None,
+            clean_env: false,
+            path_min_version: None,
+            download_on_startup: true,
+            defines: BTreeMap::new(),
+            target: None,
+            references: None,
+            list_candidates: false,
+            cleanup_grace_days: 0,
+            check_connectivity: true,
+            release_notes: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Only releases not marked as pre-releases.
+    #[default]
+    Stable,
+    /// The newest release, including pre-releases such as nightly builds. Among semver tags, the
+    /// highest version of the recent releases wins over the most recently published one.
+    Prerelease,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckOn {
+    Save,
+    Change,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionManager {
+    Mise,
+    Asdf,
+}
+
+impl VersionManager {
+    pub fn command(self) -> &'static str {
+        match self {
+            VersionManager::Mise => "mise",
+            VersionManager::Asdf => "asdf",
+        }
+    }
+
+    /// Arguments that make the manager run `binary_name` with the arguments that follow.
+    pub fn exec_args(self, binary_name: &str) -> Vec<String> {
+        match self {
+            VersionManager::Mise => vec!["exec".into(), "--".into(), binary_name.into()],
+            VersionManager::Asdf => vec!["exec".into(), binary_name.into()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinarySource {
+    /// `binary.path` from the LSP settings.
+    Settings,
+    /// The variable named by `binary_env_var`.
+    Env,
+    /// The ols selected by `version_manager`, run through it.
+    #[serde(rename = "version_manager")]
+    VersionManager,
+    /// An ols on the worktree's PATH; skipped while a version is pinned.
+    Path,
+    /// A previously downloaded ols, without checking for updates.
+    Cache,
+    /// The latest release, or the pinned one, downloaded if it isn't already.
+    Download,
+}
+
+impl BinarySource {
+    /// How the source is described in the log and the health report.
+    pub fn describe(self) -> &'static str {
+        match self {
+            BinarySource::Settings => "binary.path",
+            BinarySource::Env => "the environment",
+            BinarySource::VersionManager => "the version manager",
+            BinarySource::Path => "PATH",
+            BinarySource::Cache => "a previous download",
+            BinarySource::Download => "a new download",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionDetail {
+    /// Just the name.
+    Minimal,
+    /// The name with a procedure's signature or a field's, variable's or constant's type.
+    #[default]
+    Signature,
+    /// Everything ols puts in the detail, for every kind of completion.
+    Full,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateCheckInterval {
+    /// Only check when no downloaded binary exists at all.
+    Never,
+    /// Check at most once every 24 hours.
+    Daily,
+    /// Check on every startup.
+    #[default]
+    Startup,
+}
+
+impl OdinSettings {
+    pub fn from_lsp_settings(lsp_settings: &LspSettings) -> Result<Self> {
+        let settings: Self = match &lsp_settings.settings {
+            Some(settings) => serde_json::from_value(settings.clone())
+                .map_err(|err| format!("invalid ols settings: {err}"))?,
+            None => Self::default(),
+        };
+        // Releases are compared as strings, which only orders them in the dev-YYYY-MM form
+        if let Some(min_version) = &settings.path_min_version {
+            if crate::dev_release(min_version) != Some(min_version.as_str()) {
+                return Err(format!(
+                    "invalid ols settings: path_min_version {min_version:?} is not a release \
+                     like dev-2024-05"
+                ));
+            }
+        }
+        Ok(settings)
+    }
+
+    /// A copy for logging, with the values of `env` hidden since they can hold tokens.
+    pub fn redacted(&self) -> Self {
+        let mut settings = self.clone();
+        settings
+            .env
+            .values_mut()
+            .for_each(|value| *value = "<redacted>".to_string());
+        settings
+    }
+
+    pub fn archive_format(&self, platform: zed::Os) -> Option<ArchiveFormat> {
+        self.archive_formats.get(os_key(platform)).copied()
+    }
+
+    pub fn platform_args(&self, platform: zed::Os) -> &[String] {
+        self.platform_args
+            .get(os_key(platform))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The key used for `platform` in per-OS settings maps.
+pub fn os_key(platform: zed::Os) -> &'static str {
+    match platform {
+        zed::Os::Mac => "macos",
+        zed::Os::Linux => "linux",
+        zed::Os::Windows => "windows",
+    }
+}